@@ -0,0 +1,166 @@
+use crate::sample::{color_variance, sample_cell, SamplePoint, Source};
+use crate::shape::Triangle;
+use crate::RenderOptions;
+
+/// Settings for splitting detailed triangles into smaller ones
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveOptions {
+    /// Split triangles whose source pixels vary more than this, measured as the variance of
+    /// each channel in 8-bit units, averaged over the three channels
+    pub threshold: f32,
+    /// How many times a triangle of the regular grid may be split in four
+    pub max_depth: usize,
+}
+
+impl Default for AdaptiveOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 400.,
+            max_depth: 3,
+        }
+    }
+}
+
+/// Recursively splits the cells of the regular grid where the source image is detailed
+pub(crate) struct Subdivider<'a> {
+    source: &'a Source<'a>,
+    options: &'a RenderOptions,
+    adaptive: AdaptiveOptions,
+    /// Source pixels per document unit along each axis
+    scale: (f64, f64),
+    /// Half width and height of the smallest triangles splitting can produce
+    finest: (f32, f32),
+}
+
+impl<'a> Subdivider<'a> {
+    /// Prepares to split the cells of a grid of triangles of the given size,
+    /// laid over a document `view_width` by `view_height` units large
+    pub fn new(
+        source: &'a Source<'a>,
+        options: &'a RenderOptions,
+        adaptive: AdaptiveOptions,
+        (view_width, view_height): (f32, f32),
+        (half_width, height): (f32, f32),
+    ) -> Self {
+        let splits = (1 << adaptive.max_depth) as f32;
+        Self {
+            source,
+            options,
+            adaptive,
+            scale: (
+                source.width as f64 / view_width as f64,
+                source.height as f64 / view_height as f64,
+            ),
+            finest: (half_width / splits, height / splits),
+        }
+    }
+
+    /// Whether a regular grid cell covering the box `[min, max)` of source pixels
+    /// is detailed enough to split
+    pub fn is_detailed(&self, min: (usize, usize), max: (usize, usize)) -> bool {
+        self.should_split(min, max, 0)
+    }
+
+    /// Whether a triangle `depth` splits below the regular grid, covering the box
+    /// `[min, max)` of source pixels, should be split again. The density map decides if
+    /// there is one, and the color variance under the triangle otherwise
+    fn should_split(&self, min: (usize, usize), max: (usize, usize), depth: usize) -> bool {
+        if depth >= self.adaptive.max_depth {
+            return false;
+        }
+        match &self.options.density_map {
+            Some(map) => map.depth_at((self.source.width, self.source.height), min, max) > depth,
+            None => color_variance(self.source, min, max) > self.adaptive.threshold,
+        }
+    }
+
+    /// Splits `triangle`, which sits `depth - 1` splits below the regular grid, into four,
+    /// splitting those further while they stay detailed, and samples the remaining leaves
+    pub fn subdivide(
+        &self,
+        triangle: Triangle,
+        depth: usize,
+        cells: &mut Vec<(Triangle, [u8; 4])>,
+    ) {
+        for child in children(&triangle) {
+            let (min, max) = self.bounds(&child);
+            if self.should_split(min, max, depth) {
+                self.subdivide(child, depth + 1, cells);
+                continue;
+            }
+
+            let sample_y = match self.options.sample_at {
+                SamplePoint::Corner => child.y,
+                SamplePoint::Centroid => child.centroid().1,
+            };
+            let at = (
+                child.x as f64 * self.scale.0,
+                sample_y as f64 * self.scale.1,
+            );
+            if let Some(rgba) = sample_cell(self.source, self.options, at, min, max) {
+                cells.push((child, rgba));
+            }
+        }
+    }
+
+    /// Returns the row and column of the triangle on a grid of the finest triangles, which
+    /// is unique among the leaves. Columns are shifted so that those poking out past the left
+    /// edge still count from zero
+    pub fn fine_position(&self, triangle: &Triangle) -> (usize, usize) {
+        let shift = 1 << self.adaptive.max_depth;
+        (
+            (triangle.y / self.finest.1).round() as usize,
+            ((triangle.x / self.finest.0).round() as isize + shift) as usize,
+        )
+    }
+
+    /// Returns the box `[min, max)` of source pixels under the triangle's bounding box
+    fn bounds(&self, triangle: &Triangle) -> ((usize, usize), (usize, usize)) {
+        let (sx, sy) = self.scale;
+        let to_pixels = |x: f32, y: f32| {
+            (
+                (x.max(0.) as f64 * sx) as usize,
+                (y.max(0.) as f64 * sy) as usize,
+            )
+        };
+        (
+            to_pixels(triangle.x - triangle.half_width, triangle.y),
+            to_pixels(
+                triangle.x + triangle.half_width,
+                triangle.y + triangle.height,
+            ),
+        )
+    }
+}
+
+/// Splits a triangle into the four half-sized triangles between the midpoints of its sides:
+/// three pointing the same way at its corners, and one pointing the other way in the middle
+fn children(triangle: &Triangle) -> [Triangle; 4] {
+    let half_width = triangle.half_width / 2.;
+    let height = triangle.height / 2.;
+    let child = |x: f32, y: f32, points_up: bool| Triangle {
+        x,
+        y,
+        half_width,
+        height,
+        points_up,
+        ..*triangle
+    };
+
+    let (x, y, up) = (triangle.x, triangle.y, triangle.points_up);
+    if up {
+        [
+            child(x, y, true),
+            child(x - half_width, y + height, true),
+            child(x, y + height, false),
+            child(x + half_width, y + height, true),
+        ]
+    } else {
+        [
+            child(x - half_width, y, false),
+            child(x, y, true),
+            child(x + half_width, y, false),
+            child(x, y + height, false),
+        ]
+    }
+}
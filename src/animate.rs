@@ -0,0 +1,83 @@
+use crate::shadow::shadow_group;
+use crate::shape::Triangle;
+use crate::tile::{new_document, TriangleLayout};
+use crate::{alpha_to_opacity, encode_color, RenderOptions};
+use std::collections::{BTreeMap, HashMap};
+use svg::node::element::Animate;
+use svg::Node;
+
+/// Draws the triangle grid once, with every triangle stepping through the colors it was
+/// sampled as in each frame's layout, `fps` frames a second, over and over.
+/// Triangles left out of a frame are unfilled for it
+pub(crate) fn animate_triangles(
+    layouts: &[TriangleLayout],
+    fps: f32,
+    options: &RenderOptions,
+) -> svg::Document {
+    let mut document = new_document(layouts[0].view_width, layouts[0].view_height, options);
+    let mut shadow = shadow_group(&mut document, options);
+
+    // Every triangle of any frame, in the order the grid is laid out
+    let mut triangles: BTreeMap<(usize, usize), Triangle> = BTreeMap::new();
+    let frames: Vec<HashMap<(usize, usize), [u8; 4]>> = layouts
+        .iter()
+        .map(|layout| {
+            layout
+                .cells
+                .iter()
+                .map(|&(triangle, rgba)| {
+                    triangles.insert((triangle.row, triangle.col), triangle);
+                    ((triangle.row, triangle.col), rgba)
+                })
+                .collect()
+        })
+        .collect();
+
+    let duration = format!("{}s", frames.len() as f32 / fps);
+    let animate = |attribute: &str, values: Vec<String>| {
+        Animate::new()
+            .set("attributeName", attribute)
+            .set("values", values.join(";"))
+            .set("dur", duration.as_str())
+            .set("calcMode", "discrete")
+            .set("repeatCount", "indefinite")
+    };
+
+    for (position, triangle) in triangles {
+        let fills: Vec<String> = frames
+            .iter()
+            .map(|frame| match frame.get(&position) {
+                Some(&[r, g, b, _]) => encode_color([r, g, b]),
+                None => "none".to_string(),
+            })
+            .collect();
+        let opacities: Vec<String> = frames
+            .iter()
+            .map(|frame| {
+                let alpha = frame.get(&position).map_or(255, |rgba| rgba[3]);
+                alpha_to_opacity(alpha).to_string()
+            })
+            .collect();
+
+        let mut path = triangle.to_path(&fills[0], &options.style);
+        if fills.iter().any(|fill| *fill != fills[0]) {
+            path.append(animate("fill", fills));
+        }
+        if options.keep_alpha {
+            if opacities.iter().any(|opacity| *opacity != opacities[0]) {
+                path.append(animate("fill-opacity", opacities));
+            } else if opacities[0] != "1" {
+                path.assign("fill-opacity", opacities[0].as_str());
+            }
+        }
+        match &mut shadow {
+            Some(group) => group.append(path),
+            None => document.append(path),
+        }
+    }
+
+    match shadow {
+        Some(group) => document.add(group),
+        None => document,
+    }
+}
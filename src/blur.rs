@@ -0,0 +1,68 @@
+/// Blurs an RGB image with a Gaussian of standard deviation `sigma`, in pixels, returning
+/// data of the same size. Pixels past the edges repeat the nearest edge pixel.
+/// A sigma of 0 returns the image as it is
+pub fn gaussian_blur_rgb(image_width: usize, image_data: &[u8], sigma: f32) -> Vec<u8> {
+    blur_pixels(image_width, image_data, 3, sigma)
+}
+
+/// Like [`gaussian_blur_rgb`], for images with any number of interleaved channels per pixel
+pub(crate) fn blur_pixels(
+    image_width: usize,
+    image_data: &[u8],
+    channels: usize,
+    sigma: f32,
+) -> Vec<u8> {
+    if sigma <= 0. || image_width == 0 || image_data.is_empty() {
+        return image_data.to_vec();
+    }
+    let image_height = image_data.len() / (image_width * channels);
+    let kernel = gaussian_kernel(sigma);
+    let radius = kernel.len() / 2;
+
+    // The kernel is separable, so rows are blurred first and then columns,
+    // keeping the intermediate values unrounded
+    let mut horizontal = vec![0f32; image_data.len()];
+    for y in 0..image_height {
+        for x in 0..image_width {
+            for channel in 0..channels {
+                horizontal[(y * image_width + x) * channels + channel] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        let col = (x + i).saturating_sub(radius).min(image_width - 1);
+                        image_data[(y * image_width + col) * channels + channel] as f32 * weight
+                    })
+                    .sum();
+            }
+        }
+    }
+
+    let mut blurred = Vec::with_capacity(image_data.len());
+    for y in 0..image_height {
+        for x in 0..image_width {
+            for channel in 0..channels {
+                let value: f32 = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        let row = (y + i).saturating_sub(radius).min(image_height - 1);
+                        horizontal[(row * image_width + x) * channels + channel] * weight
+                    })
+                    .sum();
+                blurred.push(value.round().clamp(0., 255.) as u8);
+            }
+        }
+    }
+    blurred
+}
+
+/// Returns the weights of a Gaussian of the given standard deviation, normalized to sum
+/// to one, out to three standard deviations either side of the middle
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.).ceil() as isize;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2. * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
@@ -0,0 +1,62 @@
+use crate::shape::{fill_color, round_data, TileStyle, Triangle};
+use crate::{alpha_to_opacity, encode_color};
+use std::collections::HashMap;
+use svg::node::element::{path::Data as SvgData, Path as SvgPath, Style};
+use svg::Node;
+
+/// Appends the triangles to the document, or any other container of nodes, referencing each
+/// distinct color through a CSS class (`c0`, `c1`, ...) defined in a shared `<style>` block
+pub fn emit_with_classes<N: Node>(
+    document: N,
+    triangles: &[(Triangle, [u8; 4])],
+    style: &TileStyle,
+) -> N {
+    let outlines = triangles
+        .iter()
+        .map(|(tri, rgba)| (tri.append_outline(SvgData::new(), style), *rgba));
+    emit_outlines_with_classes(document, outlines, style)
+}
+
+/// Appends each outline as a path whose fill comes from a per-color CSS class
+pub(crate) fn emit_outlines_with_classes<N: Node>(
+    mut document: N,
+    outlines: impl IntoIterator<Item = (SvgData, [u8; 4])>,
+    style: &TileStyle,
+) -> N {
+    let mut class_of: HashMap<[u8; 4], usize> = HashMap::new();
+    let mut colors = vec![];
+    let mut paths = vec![];
+
+    for (data, rgba) in outlines {
+        let class = *class_of.entry(rgba).or_insert_with(|| {
+            colors.push(rgba);
+            colors.len() - 1
+        });
+        paths.push(
+            SvgPath::new()
+                .set("class", format!("c{}", class))
+                .set("d", round_data(data, style.precision)),
+        );
+    }
+
+    // Stroke settings are shared by every tile, so they live in the stylesheet too
+    let mut css = format!(
+        "path{{stroke:{};stroke-width:{}}}",
+        style.stroke, style.stroke_width
+    );
+    for (class, [r, g, b, a]) in colors.into_iter().enumerate() {
+        let color = encode_color([r, g, b]);
+        css += &format!(".c{}{{fill:{}", class, fill_color(&color, style));
+        if a < 255 {
+            css += &format!(";fill-opacity:{}", alpha_to_opacity(a));
+        }
+        css += "}";
+    }
+
+    document.append(Style::new(css));
+    for path in paths {
+        document.append(path);
+    }
+
+    document
+}
@@ -0,0 +1,221 @@
+/// Converts an 8-bit sRGB channel to linear light in `0.0..=1.0`
+pub fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts linear light in `0.0..=1.0` back to an 8-bit sRGB channel
+pub fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0., 1.);
+    let v = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    };
+    (v * 255.).round() as u8
+}
+
+/// Per-tile color adjustments, applied after sampling
+#[derive(Clone, Debug)]
+pub struct ColorOptions {
+    /// Offset added to every channel
+    pub brightness: f32,
+    /// Factor every channel is scaled by around the midpoint 128
+    pub contrast: f32,
+    /// Rotation of every color's hue, in degrees
+    pub hue: f32,
+    /// Factor every color's saturation is scaled by
+    pub saturation: f32,
+    /// Replace each color with its luminance
+    pub grayscale: bool,
+    /// Replace each color with white if its luminance reaches this level, or black otherwise
+    pub threshold: Option<u8>,
+    /// Replace each channel `v` with `255 - v`
+    pub invert: bool,
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        Self {
+            brightness: 0.,
+            contrast: 1.,
+            hue: 0.,
+            saturation: 1.,
+            grayscale: false,
+            threshold: None,
+            invert: false,
+        }
+    }
+}
+
+impl ColorOptions {
+    /// Applies every requested adjustment to a sampled color
+    pub fn apply(&self, mut rgb: [u8; 3]) -> [u8; 3] {
+        if self.brightness != 0. || self.contrast != 1. {
+            rgb = adjust(rgb, self.brightness, self.contrast);
+        }
+        if self.hue != 0. || self.saturation != 1. {
+            rgb = apply_hsl(rgb, self.hue, self.saturation);
+        }
+        if self.grayscale {
+            rgb = grayscale(rgb);
+        }
+        if let Some(level) = self.threshold {
+            rgb = threshold(rgb, level);
+        }
+        if self.invert {
+            rgb = invert(rgb);
+        }
+        rgb
+    }
+}
+
+/// Scales each channel of a color by `contrast` around 128, then adds `brightness`,
+/// clamping the result to `0..=255`
+pub fn adjust(rgb: [u8; 3], brightness: f32, contrast: f32) -> [u8; 3] {
+    rgb.map(|channel| {
+        let channel = (channel as f32 - 128.) * contrast + 128. + brightness;
+        channel.round().clamp(0., 255.) as u8
+    })
+}
+
+/// Rotates the hue of a color by `hue` degrees and scales its saturation by `saturation`,
+/// working in HSL
+pub fn apply_hsl(rgb: [u8; 3], hue: f32, saturation: f32) -> [u8; 3] {
+    let [h, s, l] = rgb_to_hsl(rgb);
+    hsl_to_rgb([
+        (h + hue).rem_euclid(360.),
+        (s * saturation).clamp(0., 1.),
+        l,
+    ])
+}
+
+/// Converts a color to hue in degrees, and saturation and lightness in `0.0..=1.0`
+fn rgb_to_hsl(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|channel| channel as f32 / 255.);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.;
+
+    let delta = max - min;
+    if delta == 0. {
+        return [0., 0., l];
+    }
+
+    let s = delta / (1. - (2. * l - 1.).abs());
+    let h = if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+
+    [h, s, l]
+}
+
+/// Inverse of [`rgb_to_hsl`]
+fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [u8; 3] {
+    let chroma = (1. - (2. * l - 1.).abs()) * s;
+    let x = chroma * (1. - ((h / 60.).rem_euclid(2.) - 1.).abs());
+    let m = l - chroma / 2.;
+
+    let (r, g, b) = match (h / 60.) as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+
+    [r, g, b].map(|channel| ((channel + m) * 255.).round().clamp(0., 255.) as u8)
+}
+
+/// Maps each channel `v` of a color to `255 - v`
+pub fn invert(rgb: [u8; 3]) -> [u8; 3] {
+    rgb.map(|channel| 255 - channel)
+}
+
+/// Returns the Rec. 601 luma of a color in `0.0..=255.0`
+pub fn luminance([r, g, b]: [u8; 3]) -> f32 {
+    0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+}
+
+/// Converts a color to the gray of equal luminance
+pub fn grayscale(rgb: [u8; 3]) -> [u8; 3] {
+    [luminance(rgb).round() as u8; 3]
+}
+
+/// Returns white if the luminance of a color reaches `level`, or black otherwise
+pub fn threshold(rgb: [u8; 3], level: u8) -> [u8; 3] {
+    if luminance(rgb) >= level as f32 {
+        [255; 3]
+    } else {
+        [0; 3]
+    }
+}
+
+/// Reduces each channel to `bits` bits of depth, spreading the remaining levels
+/// evenly over the full `0..=255` range. `bits` is clamped to `1..=8`
+pub fn posterize(rgb: [u8; 3], bits: u8) -> [u8; 3] {
+    let max_level = (1u32 << bits.clamp(1, 8)) - 1;
+    rgb.map(|channel| {
+        let level = (channel as u32 * max_level + 127) / 255;
+        ((level * 255 + max_level / 2) / max_level) as u8
+    })
+}
+
+/// Converts an sRGB color to Oklab, with `L` in `0.0..=1.0`
+pub fn rgb_to_oklab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+
+    let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+/// Converts an Oklab color back to sRGB, clamping colors outside the gamut
+pub fn oklab_to_rgb([l, a, b]: [f32; 3]) -> [u8; 3] {
+    let l_ = (l + 0.39633778 * a + 0.21580376 * b).powi(3);
+    let m_ = (l - 0.105561346 * a - 0.06385417 * b).powi(3);
+    let s_ = (l - 0.08948418 * a - 1.2914855 * b).powi(3);
+
+    [
+        4.0767417 * l_ - 3.3077116 * m_ + 0.23096994 * s_,
+        -1.268438 * l_ + 2.6097574 * m_ - 0.34131938 * s_,
+        -0.0041960863 * l_ - 0.7034186 * m_ + 1.7076147 * s_,
+    ]
+    .map(linear_to_srgb)
+}
+
+/// Converts an sRGB color to CIELAB under the D65 white point, with `L` in `0.0..=100.0`
+pub fn rgb_to_lab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+
+    // Linear sRGB to CIE XYZ, relative to the reference white
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216. / 24389. {
+            t.cbrt()
+        } else {
+            (24389. / 27. * t + 16.) / 116.
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz)]
+}
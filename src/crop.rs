@@ -0,0 +1,51 @@
+use std::borrow::Cow;
+
+/// A rectangle of source pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl CropRect {
+    /// Returns the corners `(x0, y0)` and `(x1, y1)` of the half-open rectangle, clamped
+    /// to an image of the given size but always keeping at least one pixel
+    pub(crate) fn clamped(
+        &self,
+        image_width: usize,
+        image_height: usize,
+    ) -> ((usize, usize), (usize, usize)) {
+        let x0 = self.x.min(image_width.saturating_sub(1));
+        let y0 = self.y.min(image_height.saturating_sub(1));
+        let x1 = (self.x + self.width).max(x0 + 1).min(image_width);
+        let y1 = (self.y + self.height).max(y0 + 1).min(image_height);
+        ((x0, y0), (x1, y1))
+    }
+}
+
+/// Cuts `rect` out of an image with `channels` interleaved samples per pixel,
+/// returning the width and data of the cropped image.
+/// The rectangle is clamped to the image, but always keeps at least one pixel
+pub(crate) fn crop_image(
+    image_width: usize,
+    image_data: &[u8],
+    channels: usize,
+    rect: Option<CropRect>,
+) -> (usize, Cow<'_, [u8]>) {
+    let Some(rect) = rect else {
+        return (image_width, Cow::Borrowed(image_data));
+    };
+
+    let image_height = image_data.len() / (image_width * channels);
+    let ((x0, y0), (x1, y1)) = rect.clamped(image_width, image_height);
+
+    let row_len = image_width * channels;
+    let cropped = (y0..y1)
+        .flat_map(|y| &image_data[y * row_len + x0 * channels..y * row_len + x1 * channels])
+        .copied()
+        .collect();
+
+    (x1 - x0, Cow::Owned(cropped))
+}
@@ -0,0 +1,24 @@
+use crate::encode_color;
+use crate::shape::Triangle;
+use anyhow::Result;
+use std::io::Write;
+
+/// Writes one CSV row per triangle, with the columns
+/// `row,col,centroid_x,centroid_y,points_up,hex`
+pub fn write_csv<W: Write>(mut w: W, triangles: &[(Triangle, [u8; 4])]) -> Result<()> {
+    writeln!(w, "row,col,centroid_x,centroid_y,points_up,hex")?;
+    for &(triangle, [r, g, b, _]) in triangles {
+        let (x, y) = triangle.centroid();
+        writeln!(
+            w,
+            "{},{},{},{},{},{}",
+            triangle.row,
+            triangle.col,
+            x,
+            y,
+            triangle.points_up,
+            encode_color([r, g, b])
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use crate::shape::{fill_color, round_data, round_to, TileStyle, Triangle};
+use crate::{encode_color, with_alpha};
+use svg::node::element::{path::Data as SvgData, Definitions, Path as SvgPath, Use};
+use svg::Node;
+
+/// Creates the shared outline of every upward (or downward) triangle of the given size,
+/// positioned so that a `<use>` at (x, y) lands where `triangle_at(x, y, ..)` would
+pub fn triangle_def(half_width: f32, height: f32, points_up: bool, style: &TileStyle) -> SvgPath {
+    // Every triangle shares this outline, so there is nothing to jitter
+    let unjittered = TileStyle {
+        jitter: 0.,
+        ..style.clone()
+    };
+    let data = Triangle {
+        row: 0,
+        col: 0,
+        x: 0.,
+        y: 0.,
+        half_width,
+        height,
+        points_up,
+    }
+    .append_outline(SvgData::new(), &unjittered);
+
+    SvgPath::new()
+        .set("id", def_id(points_up))
+        .set("stroke", style.stroke.as_str())
+        .set("stroke-width", style.stroke_width)
+        .set("d", round_data(data, style.precision))
+}
+
+/// References the shared triangle outline at (x, y)
+pub fn triangle_use(x: f32, y: f32, points_up: bool, color: &str) -> Use {
+    Use::new()
+        .set("href", format!("#{}", def_id(points_up)))
+        .set("x", x)
+        .set("y", y)
+        .set("fill", color)
+}
+
+/// Appends the triangles to the document, or any other container of nodes,
+/// as `<use>` references to two shared outlines.
+/// Every triangle must be the same size, as they are on the regular grid.
+pub fn emit_with_defs<N: Node>(
+    mut document: N,
+    triangles: &[(Triangle, [u8; 4])],
+    style: &TileStyle,
+) -> N {
+    let (first, _) = match triangles.first() {
+        Some(cell) => cell,
+        None => return document,
+    };
+
+    document.append(
+        Definitions::new()
+            .add(triangle_def(first.half_width, first.height, true, style))
+            .add(triangle_def(first.half_width, first.height, false, style)),
+    );
+
+    for &(tri, [r, g, b, a]) in triangles {
+        let color = encode_color([r, g, b]);
+        document.append(with_alpha(
+            triangle_use(
+                round_to(tri.x, style.precision),
+                round_to(tri.y, style.precision),
+                tri.points_up,
+                fill_color(&color, style),
+            ),
+            a,
+        ));
+    }
+
+    document
+}
+
+fn def_id(points_up: bool) -> &'static str {
+    if points_up {
+        "up"
+    } else {
+        "down"
+    }
+}
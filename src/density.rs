@@ -0,0 +1,76 @@
+use crate::color::luminance;
+use crate::load::load_image_rgba;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// A grayscale map deciding how finely each part of the image is tiled: triangles are split
+/// in four, up to `max_depth` times, the brighter the map is under them
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityMap {
+    pub width: usize,
+    pub height: usize,
+    /// One luminance sample per pixel
+    pub luma: Vec<u8>,
+    /// How many times triangles under pure white are split
+    pub max_depth: usize,
+}
+
+impl DensityMap {
+    /// Takes the luminance of each pixel of the given rgba image as the density
+    pub fn from_rgba(width: usize, rgba: &[u8], max_depth: usize) -> Self {
+        let luma: Vec<u8> = rgba
+            .chunks_exact(4)
+            .map(|px| luminance([px[0], px[1], px[2]]).round() as u8)
+            .collect();
+        Self {
+            width,
+            height: luma.len().checked_div(width).unwrap_or(0),
+            luma,
+            max_depth,
+        }
+    }
+
+    /// Loads the density from the image at the given path, picking a decoder by extension
+    pub fn load<P: AsRef<Path>>(path: P, max_depth: usize) -> Result<Self> {
+        let (width, rgba) = load_image_rgba(path).context("Loading density map")?;
+        Ok(Self::from_rgba(width, &rgba, max_depth))
+    }
+
+    /// How many times a triangle covering the box `[min, max)` of pixels of an image of the
+    /// given size should be split, from the map's mean brightness over the same part of it
+    pub(crate) fn depth_at(
+        &self,
+        (image_width, image_height): (usize, usize),
+        min: (usize, usize),
+        max: (usize, usize),
+    ) -> usize {
+        if self.luma.is_empty() {
+            return 0;
+        }
+
+        // The map is stretched over the image, whatever its size
+        let to_map = |(x, y): (usize, usize)| {
+            (
+                (x * self.width / image_width.max(1)).min(self.width - 1),
+                (y * self.height / image_height.max(1)).min(self.height - 1),
+            )
+        };
+        let (x0, y0) = to_map(min);
+        let (x1, y1) = to_map((
+            max.0.saturating_sub(1).max(min.0),
+            max.1.saturating_sub(1).max(min.1),
+        ));
+
+        let mut sum = 0u64;
+        let mut count = 0u64;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                sum += self.luma[y * self.width + x] as u64;
+                count += 1;
+            }
+        }
+
+        let mean = sum as f32 / count as f32;
+        (mean / 255. * self.max_depth as f32).round() as usize
+    }
+}
@@ -0,0 +1,66 @@
+use crate::shape::Triangle;
+use anyhow::Result;
+use std::io::Write;
+
+/// Writes the triangles as closed `LWPOLYLINE`s in a DXF drawing, with one layer per color.
+/// DXF's y axis points up, so the mosaic is mirrored vertically to keep it upright
+pub fn write_dxf<W: Write>(mut w: W, triangles: &[(Triangle, [u8; 4])]) -> Result<()> {
+    let mut layers: Vec<[u8; 3]> = Vec::new();
+    for &(_, [r, g, b, _]) in triangles {
+        if !layers.contains(&[r, g, b]) {
+            layers.push([r, g, b]);
+        }
+    }
+
+    group(&mut w, 0, "SECTION")?;
+    group(&mut w, 2, "HEADER")?;
+    group(&mut w, 9, "$ACADVER")?;
+    group(&mut w, 1, "AC1015")?;
+    group(&mut w, 0, "ENDSEC")?;
+
+    group(&mut w, 0, "SECTION")?;
+    group(&mut w, 2, "TABLES")?;
+    group(&mut w, 0, "TABLE")?;
+    group(&mut w, 2, "LAYER")?;
+    group(&mut w, 70, layers.len())?;
+    for &color in &layers {
+        let [r, g, b] = color.map(u32::from);
+        group(&mut w, 0, "LAYER")?;
+        group(&mut w, 2, layer_name(color))?;
+        group(&mut w, 70, 0)?;
+        group(&mut w, 62, 7)?;
+        group(&mut w, 420, (r << 16) | (g << 8) | b)?;
+        group(&mut w, 6, "CONTINUOUS")?;
+    }
+    group(&mut w, 0, "ENDTAB")?;
+    group(&mut w, 0, "ENDSEC")?;
+
+    group(&mut w, 0, "SECTION")?;
+    group(&mut w, 2, "ENTITIES")?;
+    for &(triangle, [r, g, b, _]) in triangles {
+        group(&mut w, 0, "LWPOLYLINE")?;
+        group(&mut w, 8, layer_name([r, g, b]))?;
+        group(&mut w, 90, 3)?;
+        group(&mut w, 70, 1)?;
+        for (x, y) in triangle.corners() {
+            group(&mut w, 10, x)?;
+            // Subtracting from zero keeps the top row at 0 rather than -0
+            group(&mut w, 20, 0. - y)?;
+        }
+    }
+    group(&mut w, 0, "ENDSEC")?;
+    group(&mut w, 0, "EOF")?;
+
+    Ok(())
+}
+
+/// Name of the layer holding every triangle of the given color
+fn layer_name([r, g, b]: [u8; 3]) -> String {
+    format!("COLOR_{:02X}{:02X}{:02X}", r, g, b)
+}
+
+/// Writes a single DXF group: a code line followed by a value line
+fn group<W: Write>(w: &mut W, code: u16, value: impl std::fmt::Display) -> Result<()> {
+    writeln!(w, "{}\n{}", code, value)?;
+    Ok(())
+}
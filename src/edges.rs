@@ -0,0 +1,33 @@
+use crate::color::luminance;
+use crate::sample::{pixel_at, Source};
+
+/// Returns the strength of the edge at pixel (x, y) of an RGB image: the magnitude of the
+/// Sobel gradient of its luminance. Pixels past the border repeat the edge of the image
+pub fn sobel_magnitude(image_width: usize, image_data: &[u8], x: usize, y: usize) -> f32 {
+    let image_height = image_data.len() / (image_width * 3);
+    let luma = |dx: isize, dy: isize| {
+        let x = (x as isize + dx).clamp(0, image_width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, image_height as isize - 1) as usize;
+        luminance(pixel_at(image_width, image_data, x, y))
+    };
+
+    let gx = (luma(1, -1) + 2. * luma(1, 0) + luma(1, 1))
+        - (luma(-1, -1) + 2. * luma(-1, 0) + luma(-1, 1));
+    let gy = (luma(-1, 1) + 2. * luma(0, 1) + luma(1, 1))
+        - (luma(-1, -1) + 2. * luma(0, -1) + luma(1, -1));
+    gx.hypot(gy)
+}
+
+/// Returns the strongest edge among the pixels in the box `[min, max)`,
+/// clamped to the image the same way the color samples are
+pub(crate) fn strongest_edge(source: &Source, min: (usize, usize), max: (usize, usize)) -> f32 {
+    let x0 = min.0.min(source.width - 1);
+    let y0 = min.1.min(source.height - 1);
+    let x1 = max.0.clamp(x0 + 1, source.width);
+    let y1 = max.1.clamp(y0 + 1, source.height);
+
+    (y0..y1)
+        .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+        .map(|(x, y)| sobel_magnitude(source.width, source.rgb, x, y))
+        .fold(0., f32::max)
+}
@@ -0,0 +1,47 @@
+use crate::shape::{round_to, Triangle};
+use anyhow::Result;
+use std::io::Write;
+
+/// Writes the triangles as filled paths in an Encapsulated PostScript page of the given size.
+/// Coordinates are taken to be in points, with y pointing down as in the SVG; PostScript's y
+/// axis points up from the bottom-left corner, so every y is flipped against `height`.
+/// PostScript has no transparency, so alpha is ignored
+pub fn write_eps<W: Write>(
+    mut w: W,
+    triangles: &[(Triangle, [u8; 4])],
+    width: f32,
+    height: f32,
+) -> Result<()> {
+    writeln!(w, "%!PS-Adobe-3.0 EPSF-3.0")?;
+    writeln!(
+        w,
+        "%%BoundingBox: 0 0 {} {}",
+        width.ceil() as u32,
+        height.ceil() as u32
+    )?;
+    writeln!(w, "%%HiResBoundingBox: 0 0 {} {}", width, height)?;
+    writeln!(w, "%%Creator: {}", env!("CARGO_PKG_NAME"))?;
+    writeln!(w, "%%EndComments")?;
+    // Takes three corners and a color: `x0 y0 x1 y1 x2 y2 r g b t`
+    writeln!(
+        w,
+        "/t {{ setrgbcolor moveto lineto lineto closepath fill }} bind def"
+    )?;
+
+    for &(triangle, [r, g, b, _]) in triangles {
+        for (x, y) in triangle.corners() {
+            write!(
+                w,
+                "{} {} ",
+                round_to(x, Some(3)),
+                round_to(height - y, Some(3))
+            )?;
+        }
+        let [r, g, b] = [r, g, b].map(|channel| round_to(channel as f32 / 255., Some(4)));
+        writeln!(w, "{} {} {} t", r, g, b)?;
+    }
+
+    writeln!(w, "showpage")?;
+    writeln!(w, "%%EOF")?;
+    Ok(())
+}
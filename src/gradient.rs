@@ -0,0 +1,152 @@
+use crate::sample::{sample_cell, SampleMode, Source};
+use crate::shape::{round_to, TileStyle, Triangle};
+use crate::{encode_color, with_alpha, RenderOptions};
+use rayon::prelude::*;
+use svg::node::element::{Definitions, LinearGradient, Stop};
+use svg::Node;
+
+/// Samples the source under each corner of every triangle, in the order of
+/// [`Triangle::corners`]. The triangles are laid over the source stretched across
+/// `image_span` of the document. Corners that can't be sampled take the triangle's own color
+pub(crate) fn corner_colors(
+    source: &Source,
+    options: &RenderOptions,
+    image_span: (f32, f32),
+    cells: &[(Triangle, [u8; 4])],
+) -> Vec<[[u8; 3]; 3]> {
+    // Each corner is a single point, so it takes the pixel under it
+    let options = &RenderOptions {
+        sample: SampleMode::Nearest,
+        supersample: None,
+        ..options.clone()
+    };
+    let (scale_x, scale_y) = (
+        source.width as f32 / image_span.0,
+        source.height as f32 / image_span.1,
+    );
+
+    cells
+        .par_iter()
+        .map(|&(triangle, [r, g, b, _])| {
+            triangle.corners().map(|(x, y)| {
+                let (x, y) = ((x * scale_x).max(0.), (y * scale_y).max(0.));
+                let pixel = (
+                    (x as usize).min(source.width - 1),
+                    (y as usize).min(source.height - 1),
+                );
+                let next = (pixel.0 + 1, pixel.1 + 1);
+                match sample_cell(source, options, (x as f64, y as f64), pixel, next) {
+                    Some([r, g, b, _]) => [r, g, b],
+                    None => [r, g, b],
+                }
+            })
+        })
+        .collect()
+}
+
+/// Creates a gradient, with the given id, shading a triangle with the given corners from
+/// one corner color to the next. It runs the way the channel changing fastest over the
+/// triangle does, so that channel is matched exactly and the others approximately.
+/// Returns `None` for triangles all of one color, which are better filled flat
+pub(crate) fn corner_gradient(
+    id: &str,
+    corners: [(f32, f32); 3],
+    colors: [[u8; 3]; 3],
+    precision: Option<u8>,
+) -> Option<LinearGradient> {
+    let [p0, p1, p2] = corners;
+    let (e1, e2) = ((p1.0 - p0.0, p1.1 - p0.1), (p2.0 - p0.0, p2.1 - p0.1));
+    let det = e1.0 * e2.1 - e1.1 * e2.0;
+    if det.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    // The gradient of the plane through each channel's three values
+    let (dx, dy) = (0..3)
+        .map(|channel| {
+            let value = |corner: usize| colors[corner][channel] as f32;
+            let (d1, d2) = (value(1) - value(0), value(2) - value(0));
+            ((d1 * e2.1 - d2 * e1.1) / det, (e1.0 * d2 - e2.0 * d1) / det)
+        })
+        .max_by(|a, b| a.0.hypot(a.1).total_cmp(&b.0.hypot(b.1)))?;
+    let length = dx.hypot(dy);
+    if length <= f32::EPSILON {
+        return None;
+    }
+    let direction = (dx / length, dy / length);
+
+    // Each corner becomes a stop where it falls along the gradient
+    let along = corners.map(|(x, y)| x * direction.0 + y * direction.1);
+    let (start, end) = along
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &t| (lo.min(t), hi.max(t)));
+    let first = corners[along.iter().position(|&t| t == start)?];
+    let span = end - start;
+
+    let mut stops: Vec<(f32, [u8; 3])> = along
+        .iter()
+        .zip(colors)
+        .map(|(&t, color)| ((t - start) / span, color))
+        .collect();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let round = |value: f32| round_to(value, precision);
+    let mut gradient = LinearGradient::new()
+        .set("id", id)
+        .set("gradientUnits", "userSpaceOnUse")
+        .set("x1", round(first.0))
+        .set("y1", round(first.1))
+        .set("x2", round(first.0 + direction.0 * span))
+        .set("y2", round(first.1 + direction.1 * span));
+    for (offset, color) in stops {
+        gradient.append(
+            Stop::new()
+                .set("offset", round(offset))
+                .set("stop-color", encode_color(color)),
+        );
+    }
+    Some(gradient)
+}
+
+/// Appends the triangles to the document, or any other container of nodes, each filled
+/// with a gradient through its corner colors. The gradients are all defined up front
+pub(crate) fn emit_with_gradients<N: Node>(
+    mut document: N,
+    cells: &[(Triangle, [u8; 4])],
+    corner_colors: &[[[u8; 3]; 3]],
+    style: &TileStyle,
+) -> N {
+    let gradients: Vec<Option<LinearGradient>> = cells
+        .par_iter()
+        .zip(corner_colors)
+        .enumerate()
+        .map(|(i, (&(triangle, _), &colors))| {
+            corner_gradient(
+                &format!("gradient-{}", i),
+                triangle.corners(),
+                colors,
+                style.precision,
+            )
+        })
+        .collect();
+
+    let mut defs = Definitions::new();
+    let mut shaded = Vec::with_capacity(gradients.len());
+    for gradient in gradients {
+        shaded.push(gradient.is_some());
+        if let Some(gradient) = gradient {
+            defs.append(gradient);
+        }
+    }
+    document.append(defs);
+
+    for (i, (&(triangle, [r, g, b, a]), shaded)) in cells.iter().zip(shaded).enumerate() {
+        let fill = if shaded {
+            format!("url(#gradient-{})", i)
+        } else {
+            encode_color([r, g, b])
+        };
+        document.append(with_alpha(triangle.to_path(&fill, style), a));
+    }
+    document
+}
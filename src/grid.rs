@@ -0,0 +1,207 @@
+use crate::sample::SamplePoint;
+use crate::shape::Triangle;
+use crate::tile::n_horizontal_triangles;
+use crate::RenderOptions;
+
+/// A cell of the triangle grid, along with where it lies over the source image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridTriangle {
+    /// The cell's position and size in the document
+    pub triangle: Triangle,
+    /// Horizontal position of the cell's sample point, in fractional source pixels
+    pub img_x: f64,
+    /// Vertical position of the cell's sample point, in fractional source pixels
+    pub img_y: f64,
+    /// Top left corner of the box of source pixels under the cell
+    pub pixel_min: (usize, usize),
+    /// Bottom right corner, exclusive, of the box of source pixels under the cell
+    pub pixel_max: (usize, usize),
+}
+
+/// Iterates over the cells of the alternating triangle grid laid over an image, row by row
+/// from the top left, without sampling any colors
+#[derive(Clone, Debug)]
+pub struct TriangleGrid {
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    /// Number of half widths the image is stretched over. Each row has one triangle more,
+    /// whose right half pokes out past the edge, so that no notch is left uncovered there
+    n_steps: usize,
+    half_width: f32,
+    height: f32,
+    sample_at: SamplePoint,
+    /// How far odd rows are shifted right, in half widths
+    stagger: f32,
+    /// Index of the next cell, counting row by row
+    next: usize,
+    /// Index one past the last cell to visit
+    end: usize,
+    /// Position of the next cell in the document
+    x: f32,
+    y: f32,
+}
+
+impl TriangleGrid {
+    /// Lays `n_vertical_tris` rows of triangles `triangle_height` tall over an image of the
+    /// given size, taken as it is after any cropping and resizing (see `tiled_size`).
+    /// The options decide the number of columns and where cells are sampled
+    pub fn new(
+        image_width: usize,
+        image_height: usize,
+        n_vertical_tris: usize,
+        triangle_height: f32,
+        options: &RenderOptions,
+    ) -> Self {
+        let n_rows = n_vertical_tris.max(1);
+        let n_steps = n_horizontal_triangles(image_width, image_height, n_rows, options);
+
+        Self {
+            image_width,
+            image_height,
+            n_rows,
+            n_steps,
+            // The ratio of half the base of a triangle to it's height is sqrt(3)
+            half_width: triangle_height / (3.0_f32).sqrt(),
+            height: triangle_height,
+            sample_at: options.sample_at,
+            stagger: options.stagger * 2.,
+            next: 0,
+            end: n_rows * (n_steps + 1),
+            x: 0.,
+            y: 0.,
+        }
+    }
+
+    /// Number of rows in the grid
+    pub fn n_rows(&self) -> usize {
+        self.n_rows
+    }
+
+    /// Number of triangles across each row, counting the last one that only half fits
+    pub fn n_cols(&self) -> usize {
+        self.n_steps + 1
+    }
+
+    /// Half of the width of the base of each triangle
+    pub fn half_width(&self) -> f32 {
+        self.half_width
+    }
+
+    /// Height of each triangle
+    pub fn triangle_height(&self) -> f32 {
+        self.height
+    }
+
+    /// Width of the document the grid fills
+    pub fn view_width(&self) -> f32 {
+        self.image_width_units() + self.row_shift(1)
+    }
+
+    /// Width the source image is stretched over, which staggered rows poke out of
+    pub fn image_width_units(&self) -> f32 {
+        self.n_steps as f32 * self.half_width
+    }
+
+    /// How far the given row is shifted right in the document
+    fn row_shift(&self, row: usize) -> f32 {
+        if row & 1 == 1 && row < self.n_rows {
+            self.stagger * self.half_width
+        } else {
+            0.
+        }
+    }
+
+    /// Height of the document the grid fills
+    pub fn view_height(&self) -> f32 {
+        self.n_rows as f32 * self.height
+    }
+
+    /// Narrows the iterator down to the cells of a single row
+    pub fn row(mut self, row: usize) -> Self {
+        let row = row.min(self.n_rows);
+        self.next = row * self.n_cols();
+        self.end = (row + 1).min(self.n_rows) * self.n_cols();
+        self.x = self.row_shift(row);
+        // Rows are stepped the same way the grid has always been laid out
+        self.y = std::iter::successors(Some(0.0), |y| Some(y + self.height))
+            .nth(row)
+            .unwrap_or(0.0);
+        self
+    }
+}
+
+impl Iterator for TriangleGrid {
+    type Item = GridTriangle;
+
+    fn next(&mut self) -> Option<GridTriangle> {
+        if self.next >= self.end {
+            return None;
+        }
+        let (row, col) = (self.next / self.n_cols(), self.next % self.n_cols());
+        let (image_width, image_height) = (self.image_width, self.image_height);
+        let (n_horiz_tris, n_vertical_tris) = (self.n_steps, self.n_rows);
+
+        let points_up = (row & 1 == 0) != (col & 1 == 0);
+
+        // The centroid is a third of the height away from the base
+        let sample_row = match self.sample_at {
+            SamplePoint::Corner => row as f64,
+            SamplePoint::Centroid if points_up => row as f64 + 2. / 3.,
+            SamplePoint::Centroid => row as f64 + 1. / 3.,
+        };
+
+        // Staggered rows sample the image as far over as they are shifted
+        let shift = self.row_shift(row) / self.half_width;
+        let to_pixels = |col: f64| {
+            let x = (col + shift as f64) * image_width as f64 / n_horiz_tris as f64;
+            (x.max(0.) as usize).min(image_width)
+        };
+        let (min_x, max_x) = if shift == 0. {
+            (
+                (col.saturating_sub(1) * image_width) / n_horiz_tris,
+                (((col + 1) * image_width) / n_horiz_tris).min(image_width),
+            )
+        } else {
+            (to_pixels(col as f64 - 1.), to_pixels(col as f64 + 1.))
+        };
+        let img_x = (col as f64 + shift as f64) * image_width as f64 / n_horiz_tris as f64;
+
+        // The triangle spans one half-width on either side of its grid position
+        let cell = GridTriangle {
+            triangle: Triangle {
+                row,
+                col,
+                x: self.x,
+                y: self.y,
+                half_width: self.half_width,
+                height: self.height,
+                points_up,
+            },
+            img_x: if shift == 0. {
+                (col * image_width) as f64 / n_horiz_tris as f64
+            } else {
+                img_x.min(image_width as f64)
+            },
+            img_y: sample_row * image_height as f64 / n_vertical_tris as f64,
+            pixel_min: (min_x, (row * image_height) / n_vertical_tris),
+            pixel_max: (max_x, ((row + 1) * image_height) / n_vertical_tris),
+        };
+
+        self.next += 1;
+        if col == n_horiz_tris {
+            self.x = self.row_shift(row + 1);
+            self.y += self.height;
+        } else {
+            self.x += self.half_width;
+        }
+        Some(cell)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end.saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for TriangleGrid {}
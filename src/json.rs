@@ -0,0 +1,32 @@
+use crate::shape::Triangle;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// A triangle as it appears in the JSON export
+#[derive(Serialize)]
+struct JsonTriangle {
+    /// Horizontal center of the triangle
+    x: f32,
+    /// Top of the triangle
+    y: f32,
+    points_up: bool,
+    rgb: [u8; 3],
+}
+
+/// Writes the triangles as a JSON array of `{ x, y, points_up, rgb }` objects
+pub fn write_json<W: Write>(mut w: W, triangles: &[(Triangle, [u8; 4])]) -> Result<()> {
+    let triangles: Vec<JsonTriangle> = triangles
+        .iter()
+        .map(|&(triangle, [r, g, b, _])| JsonTriangle {
+            x: triangle.x,
+            y: triangle.y,
+            points_up: triangle.points_up,
+            rgb: [r, g, b],
+        })
+        .collect();
+
+    serde_json::to_writer(&mut w, &triangles)?;
+    writeln!(w)?;
+    Ok(())
+}
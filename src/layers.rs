@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+use svg::node::element::Group;
+use svg::Node;
+
+/// Appends the cells to the document, or any other container of nodes, in one
+/// `<g id="color-RRGGBB">` group per distinct color, ordered by hex code.
+/// `emit` draws the cells of a single color into their group
+pub fn emit_layers_by_color<N: Node, T: Copy>(
+    mut document: N,
+    cells: &[(T, [u8; 4])],
+    emit: impl Fn(Group, &[(T, [u8; 4])]) -> Group,
+) -> N {
+    let mut layers: BTreeMap<[u8; 3], Vec<(T, [u8; 4])>> = BTreeMap::new();
+    for &(cell, [r, g, b, a]) in cells {
+        layers
+            .entry([r, g, b])
+            .or_default()
+            .push((cell, [r, g, b, a]));
+    }
+
+    for (rgb, cells) in layers {
+        let group = Group::new().set("id", layer_id(rgb));
+        document.append(emit(group, &cells));
+    }
+
+    document
+}
+
+/// Returns the id of the group holding every tile of the given color
+pub fn layer_id([r, g, b]: [u8; 3]) -> String {
+    format!("color-{:02X}{:02X}{:02X}", r, g, b)
+}
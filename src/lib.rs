@@ -0,0 +1,611 @@
+mod adaptive;
+mod animate;
+mod blur;
+mod classes;
+mod color;
+mod crop;
+mod csv;
+mod defs;
+mod density;
+mod dxf;
+mod edges;
+mod eps;
+mod gradient;
+mod grid;
+mod json;
+mod layers;
+mod load;
+mod merge;
+mod metadata;
+mod mirror;
+mod palette;
+mod quantize;
+mod raster;
+mod resize;
+mod sample;
+mod shadow;
+mod shape;
+mod stats;
+mod stream;
+mod tile;
+mod units;
+pub use adaptive::AdaptiveOptions;
+use anyhow::bail;
+use blur::blur_pixels;
+pub use blur::gaussian_blur_rgb;
+pub use classes::emit_with_classes;
+pub use color::{
+    adjust, apply_hsl, grayscale, invert, linear_to_srgb, luminance, oklab_to_rgb, posterize,
+    rgb_to_lab, rgb_to_oklab, srgb_to_linear, threshold, ColorOptions,
+};
+use crop::crop_image;
+pub use crop::CropRect;
+pub use csv::write_csv;
+pub use defs::{emit_with_defs, triangle_def, triangle_use};
+pub use density::DensityMap;
+pub use dxf::write_dxf;
+pub use edges::sobel_magnitude;
+pub use eps::write_eps;
+pub use grid::{GridTriangle, TriangleGrid};
+pub use json::write_json;
+pub use layers::{emit_layers_by_color, layer_id};
+#[cfg(feature = "bmp")]
+pub use load::load_bmp_rgb;
+#[cfg(feature = "gif")]
+pub use load::load_gif_rgb;
+#[cfg(feature = "jpeg")]
+pub use load::load_jpeg_rgb;
+#[cfg(feature = "pnm")]
+pub use load::load_pnm_rgb;
+#[cfg(feature = "tga")]
+pub use load::load_tga_rgb;
+#[cfg(feature = "tiff")]
+pub use load::load_tiff_rgb;
+#[cfg(feature = "webp")]
+pub use load::load_webp_rgb;
+pub use load::{
+    composite_over, image_info, is_supported_image, load_image_rgb, load_image_rgba,
+    load_input_rgba, load_png_from_path, load_png_rgb, load_png_rgba, load_raw_input_rgba,
+    load_raw_rgb, load_raw_rgba, open_input_file, open_input_file_mapping, png_info, rgba_to_rgb,
+    ImageInfo, InputFile,
+};
+pub use merge::{merge_by_color, region_borders};
+pub use metadata::DocumentMetadata;
+use mirror::mirror_pixels;
+pub use palette::{color_distance, load_palette, nearest_palette, parse_hex_color, PaletteSpace};
+pub use quantize::quantize_kmeans;
+pub use raster::{render_to_png, view_box_size};
+use resize::resize_pixels;
+pub use resize::resize_rgb;
+use sample::Source;
+pub use sample::{
+    average_triangle_color, median_triangle_color, pixel_at, sample_bilinear, supersample_color,
+    AverageSpace, SampleMode, SamplePoint,
+};
+pub use shadow::DropShadow;
+pub use shape::{
+    diamond_at, hexagon_at, square_at, triangle_at, Orientation, Shape, TileElement, TileStyle,
+    Triangle,
+};
+pub use stats::RenderStats;
+use std::borrow::Cow;
+use std::io::Write;
+use svg::Node;
+use tile::{
+    layout_triangles, n_diamond_columns, n_hex_columns, n_horizontal_triangles, n_square_columns,
+    tile_diamonds, tile_hexagons, tile_squares, tile_triangles,
+};
+pub use units::{PhysicalWidth, Unit};
+
+/// Knobs controlling how the mosaic is rendered
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// Appearance shared by every tile
+    pub style: TileStyle,
+    /// Only tile this rectangle of the source image
+    pub crop: Option<CropRect>,
+    /// Blur the (cropped) source image with a Gaussian of this standard deviation, in source
+    /// pixels, before resizing and tiling it, to tame noise and aliasing. 0 leaves it sharp
+    pub blur: f32,
+    /// Rescale the (cropped) source image to this width and height before tiling
+    pub resize: Option<(usize, usize)>,
+    /// Tile the (cropped and resized) image next to its reflections across its right and
+    /// bottom edges, so the colors match up when the mosaic is repeated. Derived triangle
+    /// counts are kept even so the grid lines up too, but rows must be even for that as well
+    pub mirror: bool,
+    /// Sample the (cropped) image mirrored left to right
+    pub flip_h: bool,
+    /// Sample the (cropped) image mirrored top to bottom
+    pub flip_v: bool,
+    /// Number of tiles across each row, instead of deriving it from the image's aspect ratio
+    pub horizontal: Option<usize>,
+    /// Derive the number of triangles across each row the way older versions did,
+    /// truncating twice, which distorts the aspect ratio of small grids
+    pub legacy_aspect: bool,
+    /// The kind of tile the image is divided into
+    pub shape: Shape,
+    /// Shift every other row of triangles right by this fraction of a triangle's base,
+    /// widening the document to fit. Tiles other than triangles ignore this
+    pub stagger: f32,
+    /// Which way the rows of triangles run. With `Vertical`, the number of rows counts the
+    /// columns of left and right pointing triangles instead, and `horizontal` the triangles
+    /// down each of them. Hexagons and squares ignore this
+    pub orientation: Orientation,
+    /// How each triangle's color is picked from the source image
+    pub sample: SampleMode,
+    /// Where within each triangle its color is sampled. Hexagons and squares are always
+    /// sampled at their centers
+    pub sample_at: SamplePoint,
+    /// Split triangles of the regular grid into smaller ones wherever the source is detailed.
+    /// The rows and columns of the triangles then count triangles of the smallest size
+    pub adaptive: Option<AdaptiveOptions>,
+    /// Split triangles of the regular grid into smaller ones the brighter this map is under
+    /// them, instead of where the source is detailed. Takes precedence over `adaptive`
+    pub density_map: Option<DensityMap>,
+    /// Average an N x N grid of source pixels spread over each tile's bounds.
+    /// Takes precedence over `sample`
+    pub supersample: Option<usize>,
+    /// Color space used when averaging source pixels
+    pub average_space: AverageSpace,
+    /// Adjustments applied to each sampled color
+    pub color: ColorOptions,
+    /// Reduce each channel to this many bits (1 to 8)
+    pub posterize: Option<u8>,
+    /// Snap every tile to a palette of at most this many colors, found with k-means
+    pub colors: Option<usize>,
+    /// Snap every tile to the closest of these colors. Takes precedence over `colors`
+    pub palette: Option<Vec<[u8; 3]>>,
+    /// Color space in which tiles are matched against `palette`, the `colors` palette
+    /// and `chroma_key`
+    pub palette_space: PaletteSpace,
+    /// Fill every tile with an entry of `palette` picked at random, seeded by the style's
+    /// `seed`, rather than its sampled color. The image then only serves as a mask:
+    /// tiles where it is light or mostly transparent are left out
+    pub randomize_colors: bool,
+    /// Diffuse the error left by `posterize`, `colors` and `palette` over neighboring tiles
+    /// with Floyd–Steinberg dithering
+    pub dither: bool,
+    /// The element each triangle is drawn with. Merged, class-based and `defs` output
+    /// always use paths
+    pub element: TileElement,
+    /// Fill each triangle with a `<linearGradient>` through the colors under its three corners,
+    /// smoothing over the facets. Takes precedence over `defs`, `classes`, `merge` and
+    /// `layers_by_color`, and leaves the corner colors unquantized. Other shapes ignore this
+    pub gradient: bool,
+    /// Merge adjacent same-colored triangles into a single path
+    pub merge: bool,
+    /// Reference colors through CSS classes in a shared stylesheet instead of per-path fills
+    pub classes: bool,
+    /// Share two triangle outlines through `<defs>` and place every triangle with `<use>`.
+    /// Takes precedence over `merge` and `classes`, but is ignored with `adaptive` or a
+    /// `density_map`
+    pub defs: bool,
+    /// Wrap the tiles of each color in a `<g id="color-RRGGBB">` group, ordered by hex code.
+    /// Ignored with `classes`, or `defs` on the regular grid
+    pub layers_by_color: bool,
+    /// Stroke the borders between differently colored triangles in this color, `stroke_width`
+    /// wide, over the tiles. Hexagons and squares ignore this
+    pub region_borders: Option<String>,
+    /// Blank space added around the mosaic on every side, in the same units as the tiles
+    pub margin: f32,
+    /// Size the document should be displayed at, set through its `width` and `height`
+    pub physical_width: Option<PhysicalWidth>,
+    /// Provenance written at the top of the document, after a generator comment
+    pub metadata: Option<DocumentMetadata>,
+    /// Fill color of a rectangle drawn behind the whole mosaic
+    pub background: Option<String>,
+    /// Shadow cast by the tiles, which are drawn in a group filtered to cast it
+    pub shadow: Option<DropShadow>,
+    /// Blend the source image over this color, by its alpha, before tiling it, leaving every
+    /// pixel opaque. Keeps anti-aliased edges from darkening towards the transparent color
+    pub composite_over: Option<[u8; 3]>,
+    /// Carry the source image's alpha through as `fill-opacity` on each tile
+    pub keep_alpha: bool,
+    /// Leave out tiles whose sampled alpha is below this value
+    pub skip_transparent: Option<u8>,
+    /// Leave out tiles whose sampled color lies within `chroma_tolerance` of this color,
+    /// measured in `palette_space`
+    pub chroma_key: Option<[u8; 3]>,
+    /// Distance from `chroma_key` within which tiles are left out
+    pub chroma_tolerance: f32,
+    /// Leave out tiles without an edge under them, keeping only those where the Sobel gradient
+    /// of the source's luminance reaches this magnitude somewhere
+    pub edges: Option<f32>,
+    /// Draw every tile in this color, with a `fill-opacity` following the darkness of the
+    /// sampled color, so dark areas come out dense and light ones faint
+    pub ink: Option<[u8; 3]>,
+    /// Advanced by one for every row of tiles sampled
+    pub progress: Option<indicatif::ProgressBar>,
+}
+
+impl RenderOptions {
+    /// How triangles of the regular grid are split, whether by detail or the density map
+    pub(crate) fn splitting(&self) -> Option<AdaptiveOptions> {
+        match &self.density_map {
+            Some(map) => Some(AdaptiveOptions {
+                threshold: 0.,
+                max_depth: map.max_depth,
+            }),
+            None => self.adaptive,
+        }
+    }
+}
+
+/// Tiles the given RGB image with triangles, returning the assembled document.
+/// An image without any pixels gives a document with no tiles
+pub fn build_document(
+    image_width: usize,
+    image_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+) -> svg::Document {
+    build_document_with_options(
+        image_width,
+        image_data,
+        n_vertical_tris,
+        triangle_height,
+        &RenderOptions::default(),
+    )
+}
+
+/// Like [`build_document`], but with control over how the mosaic is rendered
+pub fn build_document_with_options(
+    image_width: usize,
+    image_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> svg::Document {
+    let (image_width, image_data) = prepare_image(image_width, image_data, 3, options);
+    let source = Source::new(image_width, &image_data, None);
+    tile_source(&source, n_vertical_tris, triangle_height, options).0
+}
+
+/// Like [`build_document_with_options`], but for RGBA data.
+/// The alpha channel is carried through to the output if the options ask for it
+pub fn build_document_rgba(
+    image_width: usize,
+    rgba_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> svg::Document {
+    build_document_rgba_with_stats(
+        image_width,
+        rgba_data,
+        n_vertical_tris,
+        triangle_height,
+        options,
+    )
+    .0
+}
+
+/// Like [`build_document_rgba`], but also counts what ended up in the document
+pub fn build_document_rgba_with_stats(
+    image_width: usize,
+    rgba_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    let (image_width, rgba_data) = prepare_image(image_width, rgba_data, 4, options);
+    let (rgb, alpha) = split_alpha(&rgba_data, options);
+    let source = Source::new(image_width, &rgb, Some(&alpha));
+    tile_source(&source, n_vertical_tris, triangle_height, options)
+}
+
+/// Like [`build_document_rgba`], but writes the SVG to `w` as the rows are sampled rather
+/// than building the document first, so huge grids fit in memory. Fails for options that
+/// need every tile at once: other shapes or orientations, `merge`, `classes`, `defs`,
+/// `layers_by_color`, `gradient`, `colors`, `dither`, `shadow` and `region_borders`
+pub fn stream_document<W: Write>(
+    w: W,
+    image_width: usize,
+    rgba_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    stream_document_with_stats(
+        w,
+        image_width,
+        rgba_data,
+        n_vertical_tris,
+        triangle_height,
+        options,
+    )
+    .map(|_| ())
+}
+
+/// Like [`stream_document`], but also counts what was written
+pub fn stream_document_with_stats<W: Write>(
+    w: W,
+    image_width: usize,
+    rgba_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> anyhow::Result<RenderStats> {
+    let (image_width, rgba_data) = prepare_image(image_width, rgba_data, 4, options);
+    let (rgb, alpha) = split_alpha(&rgba_data, options);
+    let source = Source::new(image_width, &rgb, Some(&alpha));
+    stream::stream_triangles(w, &source, n_vertical_tris, triangle_height, options)
+}
+
+/// Tiles each frame, given as (width, rgba data), with the same triangle grid and returns a
+/// document animating every triangle through its colors, `fps` frames a second, on a loop.
+/// The frames must all be the same size. The shape and orientation in the options are
+/// ignored, and neither `adaptive` nor `density_map` is supported, since frames would split
+/// their grids differently
+pub fn build_animated_document(
+    frames: &[(usize, Vec<u8>)],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    fps: f32,
+    options: &RenderOptions,
+) -> anyhow::Result<svg::Document> {
+    let Some((first_width, first_data)) = frames.first() else {
+        bail!("An animation needs at least one frame");
+    };
+    if fps <= 0. || fps.is_nan() {
+        bail!("Frame rate must be greater than zero");
+    }
+    if options.splitting().is_some() {
+        bail!("Adaptive grids can't be animated");
+    }
+    if options.style.outline_only {
+        bail!("Unfilled tiles have no colors to animate");
+    }
+    if let Some(idx) = frames
+        .iter()
+        .position(|(width, data)| width != first_width || data.len() != first_data.len())
+    {
+        bail!("Frame {} is not the same size as the first frame", idx);
+    }
+
+    let layouts: Vec<_> = frames
+        .iter()
+        .map(|(image_width, rgba_data)| {
+            let (image_width, rgba_data) = prepare_image(*image_width, rgba_data, 4, options);
+            let (rgb, alpha) = split_alpha(&rgba_data, options);
+            let source = Source::new(image_width, &rgb, Some(&alpha));
+            layout_triangles(&source, n_vertical_tris, triangle_height, options)
+        })
+        .collect();
+    Ok(animate::animate_triangles(&layouts, fps, options))
+}
+
+/// Decodes the PNG held in memory and tiles it with the default options, returning the
+/// serialized SVG. Touches neither the filesystem nor the terminal, so it suits the browser
+pub fn render_svg_from_bytes(
+    png_bytes: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+) -> anyhow::Result<String> {
+    let (image_width, rgba_data) = load_png_rgba(png_bytes)?;
+    let document = build_document_rgba(
+        image_width,
+        &rgba_data,
+        n_vertical_tris,
+        triangle_height,
+        &RenderOptions::default(),
+    );
+    Ok(document.to_string())
+}
+
+/// Lays out the triangle grid over the given RGBA image without building a document,
+/// returning each triangle along with its color.
+/// The shape and orientation in the options are ignored
+pub fn triangle_cells_rgba(
+    image_width: usize,
+    rgba_data: &[u8],
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> Vec<(Triangle, [u8; 4])> {
+    let (image_width, rgba_data) = prepare_image(image_width, rgba_data, 4, options);
+    let (rgb, alpha) = split_alpha(&rgba_data, options);
+    let source = Source::new(image_width, &rgb, Some(&alpha));
+    layout_triangles(&source, n_vertical_tris, triangle_height, options).cells
+}
+
+/// Returns the (columns, rows) of the triangle grid `build_document_rgba` would lay over
+/// an image of the given size, after cropping and resizing it as the options ask
+pub fn triangle_grid_size(
+    image_width: usize,
+    image_height: usize,
+    n_vertical_tris: usize,
+    options: &RenderOptions,
+) -> (usize, usize) {
+    let (image_width, image_height) = tiled_size(image_width, image_height, options);
+    let n_vertical_tris = n_vertical_tris.max(1);
+
+    // Each row ends with a triangle poking half out past the edge, on top of the ones
+    // the image is divided into
+    if options.orientation == Orientation::Vertical {
+        let n_along = n_horizontal_triangles(image_height, image_width, n_vertical_tris, options);
+        return (n_vertical_tris, n_along + 1);
+    }
+
+    let n_horiz_tris = n_horizontal_triangles(image_width, image_height, n_vertical_tris, options);
+    (n_horiz_tris + 1, n_vertical_tris)
+}
+
+/// Returns the (columns, rows) of the grid of whichever tiles the options ask for,
+/// as `build_document_rgba` would lay it over an image of the given size.
+/// Tiles split by `adaptive` or the `density_map` are not counted
+pub fn tile_grid_size(
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    tile_height: f32,
+    options: &RenderOptions,
+) -> (usize, usize) {
+    let n_rows = n_rows.max(1);
+    match options.shape {
+        Shape::Triangle => triangle_grid_size(image_width, image_height, n_rows, options),
+        Shape::Hex => {
+            let (image_width, image_height) = tiled_size(image_width, image_height, options);
+            let n_cols = n_hex_columns(image_width, image_height, n_rows, tile_height, options);
+            (n_cols, n_rows)
+        }
+        Shape::Square => {
+            let (image_width, image_height) = tiled_size(image_width, image_height, options);
+            let n_cols = n_square_columns(image_width, image_height, n_rows, options);
+            (n_cols, n_rows)
+        }
+        Shape::Diamond => {
+            let (image_width, image_height) = tiled_size(image_width, image_height, options);
+            let n_cols = n_diamond_columns(image_width, image_height, n_rows, options);
+            (n_cols, n_rows)
+        }
+    }
+}
+
+/// Returns the most rows, up to `n_rows`, for which [`tile_grid_size`] lays at most
+/// `max_tiles` tiles over an image of the given size, or `None` if even one row has more
+pub fn rows_within_tile_count(
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    tile_height: f32,
+    options: &RenderOptions,
+    max_tiles: usize,
+) -> Option<usize> {
+    let count = |n_rows: usize| {
+        let (n_cols, n_rows) =
+            tile_grid_size(image_width, image_height, n_rows, tile_height, options);
+        n_cols * n_rows
+    };
+    let n_rows = n_rows.max(1);
+    if count(n_rows) <= max_tiles {
+        return Some(n_rows);
+    }
+    if count(1) > max_tiles {
+        return None;
+    }
+
+    // More rows never mean fewer tiles, so search for the most rows that still fit
+    let (mut fits, mut too_many) = (1, n_rows);
+    while too_many - fits > 1 {
+        let middle = (fits + too_many) / 2;
+        if count(middle) <= max_tiles {
+            fits = middle;
+        } else {
+            too_many = middle;
+        }
+    }
+    Some(fits)
+}
+
+/// Returns the size an image of the given size is tiled at,
+/// after cropping, resizing and mirroring it as the options ask
+pub fn tiled_size(
+    image_width: usize,
+    image_height: usize,
+    options: &RenderOptions,
+) -> (usize, usize) {
+    let (image_width, image_height) = match options.crop {
+        Some(rect) => {
+            let ((x0, y0), (x1, y1)) = rect.clamped(image_width, image_height);
+            (x1 - x0, y1 - y0)
+        }
+        None => (image_width, image_height),
+    };
+    let (image_width, image_height) = options.resize.unwrap_or((image_width, image_height));
+    if options.mirror {
+        (image_width * 2, image_height * 2)
+    } else {
+        (image_width, image_height)
+    }
+}
+
+/// Crops, resizes and mirrors the source image as the options ask.
+/// Images without a single whole pixel come out empty
+fn prepare_image<'a>(
+    image_width: usize,
+    image_data: &'a [u8],
+    channels: usize,
+    options: &RenderOptions,
+) -> (usize, Cow<'a, [u8]>) {
+    if image_width == 0 || image_data.len() < image_width * channels {
+        return (0, Cow::Borrowed(&[]));
+    }
+    let (image_width, image_data) = crop_image(image_width, image_data, channels, options.crop);
+    let image_data = if options.blur > 0. {
+        Cow::Owned(blur_pixels(
+            image_width,
+            &image_data,
+            channels,
+            options.blur,
+        ))
+    } else {
+        image_data
+    };
+    let (image_width, image_data) = match options.resize {
+        Some((new_width, new_height)) => {
+            let (image_width, resized) =
+                resize_pixels(image_width, &image_data, channels, new_width, new_height);
+            (image_width, Cow::Owned(resized))
+        }
+        None => (image_width, image_data),
+    };
+    if options.mirror {
+        let (image_width, mirrored) = mirror_pixels(image_width, &image_data, channels);
+        (image_width, Cow::Owned(mirrored))
+    } else {
+        (image_width, image_data)
+    }
+}
+
+/// Splits RGBA data into its RGB and alpha planes, compositing it over the background
+/// color first if the options ask for it
+fn split_alpha(rgba_data: &[u8], options: &RenderOptions) -> (Vec<u8>, Vec<u8>) {
+    if let Some(background) = options.composite_over {
+        let rgb = composite_over(rgba_data, background);
+        return (rgb, vec![255; rgba_data.len() / 4]);
+    }
+    let rgb = rgba_to_rgb(rgba_data);
+    let alpha = rgba_data.chunks_exact(4).map(|px| px[3]).collect();
+    (rgb, alpha)
+}
+
+fn tile_source(
+    source: &Source,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    match options.shape {
+        Shape::Triangle => tile_triangles(source, n_vertical_tris, triangle_height, options),
+        Shape::Hex => tile_hexagons(source, n_vertical_tris, triangle_height, options),
+        Shape::Square => tile_squares(source, n_vertical_tris, triangle_height, options),
+        Shape::Diamond => tile_diamonds(source, n_vertical_tris, triangle_height, options),
+    }
+}
+
+/// Encodes an RGB color as a `#RRGGBB` hex string,
+/// or the `#RGB` shorthand when every channel repeats its hex digit
+pub fn encode_color([r, g, b]: [u8; 3]) -> String {
+    if [r, g, b]
+        .iter()
+        .all(|channel| channel >> 4 == channel & 0xF)
+    {
+        format!("#{:X}{:X}{:X}", r & 0xF, g & 0xF, b & 0xF)
+    } else {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+}
+
+/// Converts an 8-bit alpha value to an SVG opacity in `0.0..=1.0`
+pub(crate) fn alpha_to_opacity(alpha: u8) -> f32 {
+    alpha as f32 / 255.
+}
+
+/// Sets `fill-opacity` on tiles that aren't fully opaque
+pub(crate) fn with_alpha<N: Node>(mut node: N, alpha: u8) -> N {
+    if alpha < 255 {
+        node.assign("fill-opacity", alpha_to_opacity(alpha));
+    }
+    node
+}
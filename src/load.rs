@@ -0,0 +1,392 @@
+use anyhow::{bail, Context, Result};
+use image::{ColorType, ImageDecoder, ImageFormat};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+/// Files at least this big are memory-mapped rather than read through a buffer
+const MMAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// An input file opened for reading. Large files are mapped into memory, so decoders that
+/// need every byte at once can read them in place instead of copying them first
+pub enum InputFile {
+    Mapped(Cursor<Mmap>),
+    Buffered(BufReader<File>),
+}
+
+impl Read for InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            InputFile::Mapped(cursor) => cursor.read(buf),
+            InputFile::Buffered(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Opens the file at the given path, mapping it into memory if it is large
+pub fn open_input_file<P: AsRef<Path>>(path: P) -> Result<InputFile> {
+    open_input_file_mapping(path, MMAP_THRESHOLD)
+}
+
+/// Like [`open_input_file`], mapping the file into memory if it holds at least `threshold` bytes
+pub fn open_input_file_mapping<P: AsRef<Path>>(path: P, threshold: u64) -> Result<InputFile> {
+    let file = File::open(path).context("Opening file")?;
+    let len = file.metadata().context("Reading file size")?.len();
+    if len < threshold {
+        return Ok(InputFile::Buffered(BufReader::new(file)));
+    }
+
+    // SAFETY: the map is only ever read. Like any reader, it sees garbage if another
+    // process truncates or rewrites the file while it is being decoded
+    match unsafe { Mmap::map(&file) } {
+        Ok(map) => Ok(InputFile::Mapped(Cursor::new(map))),
+        // Platforms and filesystems without mmap are read the usual way
+        Err(_) => Ok(InputFile::Buffered(BufReader::new(file))),
+    }
+}
+
+/// Returns (width, rgb data) for the image at the given path, picking a decoder by extension
+pub fn load_image_rgb<P: AsRef<Path>>(path: P) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_image_rgba(path)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgba data) for the image at the given path, picking a decoder by extension
+pub fn load_image_rgba<P: AsRef<Path>>(path: P) -> Result<(usize, Vec<u8>)> {
+    let path = path.as_ref();
+    // Unsupported formats fail before the file is opened
+    image_crate_format(path)?;
+    load_input_rgba(open_input_file(path)?, path)
+}
+
+/// Returns (width, rgba data) for the image in the opened file, picking a decoder by the
+/// extension of the path it was opened from. Mapped files are decoded in place
+pub fn load_input_rgba(input: InputFile, path: &Path) -> Result<(usize, Vec<u8>)> {
+    match (input, image_crate_format(path)?) {
+        (InputFile::Mapped(map), Some(format)) => {
+            decode_with_image_crate(Cursor::new(&map.get_ref()[..]), format)
+        }
+        (InputFile::Buffered(reader), Some(format)) => decode_with_image_crate(reader, format),
+        (InputFile::Mapped(map), None) => load_png_rgba(&map.get_ref()[..]),
+        (InputFile::Buffered(reader), None) => load_png_rgba(reader),
+    }
+}
+
+/// Whether the file at the given path has the extension of an image this build can load
+pub fn is_supported_image<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    let is_png = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    is_png || matches!(image_crate_format(path), Ok(Some(_)))
+}
+
+/// Header details of an image file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: usize,
+    pub height: usize,
+    /// Color type as stored in the file, such as `Rgb` or `GrayscaleAlpha`
+    pub color_type: String,
+    /// Bits per sample as stored in the file
+    pub bit_depth: u8,
+}
+
+/// Reads the header of the image at the given path, picking a decoder by extension
+pub fn image_info<P: AsRef<Path>>(path: P) -> Result<ImageInfo> {
+    let path = path.as_ref();
+    let format = image_crate_format(path)?;
+
+    match (open_input_file(path)?, format) {
+        (InputFile::Mapped(map), Some(format)) => {
+            info_with_image_crate(Cursor::new(&map.get_ref()[..]), format)
+        }
+        (InputFile::Buffered(reader), Some(format)) => info_with_image_crate(reader, format),
+        (input, None) => png_info(input),
+    }
+}
+
+/// Reads the header of the given PNG image reader
+pub fn png_info<R: Read>(r: R) -> Result<ImageInfo> {
+    let reader = png::Decoder::new(r)
+        .read_info()
+        .context("Creating reader")?;
+    let info = reader.info();
+
+    Ok(ImageInfo {
+        width: info.width as usize,
+        height: info.height as usize,
+        color_type: format!("{:?}", info.color_type),
+        bit_depth: info.bit_depth as u8,
+    })
+}
+
+/// Picks the `image` crate format for the path's extension, or `None` for PNGs,
+/// which are decoded by the `png` crate. Fails for formats whose Cargo feature is disabled
+fn image_crate_format(path: &Path) -> Result<Option<ImageFormat>> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => enabled(ImageFormat::Jpeg, cfg!(feature = "jpeg"), "jpeg"),
+        Some("bmp") => enabled(ImageFormat::Bmp, cfg!(feature = "bmp"), "bmp"),
+        Some("gif") => enabled(ImageFormat::Gif, cfg!(feature = "gif"), "gif"),
+        Some("pnm") | Some("ppm") | Some("pgm") | Some("pbm") => {
+            enabled(ImageFormat::Pnm, cfg!(feature = "pnm"), "pnm")
+        }
+        Some("tga") => enabled(ImageFormat::Tga, cfg!(feature = "tga"), "tga"),
+        Some("webp") => enabled(ImageFormat::WebP, cfg!(feature = "webp"), "webp"),
+        Some("tif") | Some("tiff") => enabled(ImageFormat::Tiff, cfg!(feature = "tiff"), "tiff"),
+        _ => Ok(None),
+    }
+}
+
+/// Passes the format through if this build was compiled with the Cargo feature decoding it
+fn enabled(format: ImageFormat, is_enabled: bool, feature: &str) -> Result<Option<ImageFormat>> {
+    if !is_enabled {
+        bail!(
+            "{:?} images are not enabled in this build; rebuild with the `{}` feature",
+            format,
+            feature
+        );
+    }
+    Ok(Some(format))
+}
+
+/// Returns (width, rgb data) for the PNG image at the given path
+pub fn load_png_from_path<P: AsRef<Path>>(path: P) -> Result<(usize, Vec<u8>)> {
+    load_png_rgb(open_input_file(path)?)
+}
+
+/// Returns (width, rgb data) for the given PNG image reader
+pub fn load_png_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_png_rgba(r)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgba data) for the given PNG image reader. Opaque images get an alpha of 255
+pub fn load_png_rgba<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(r);
+    // Resolve palettes to RGB(A) and scale sub-8-bit grayscale up to 8 bits
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().context("Creating reader")?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).context("Reading frame")?;
+
+    buf.truncate(info.buffer_size());
+
+    let buf: Vec<u8> = match info.bit_depth {
+        png::BitDepth::Eight => buf,
+        // Samples are big-endian, so keeping the first byte of each is `(v >> 8) as u8`
+        png::BitDepth::Sixteen => buf.chunks_exact(2).map(|sample| sample[0]).collect(),
+        other => bail!("Bit depth {:?} unsupported!", other),
+    };
+
+    let buf: Vec<u8> = match info.color_type {
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|px| [px[0], px[1], px[2], 255])
+            .collect(),
+        png::ColorType::Rgba => buf,
+        png::ColorType::Grayscale => buf.iter().flat_map(|&px| [px, px, px, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|px| [px[0], px[0], px[0], px[1]])
+            .collect(),
+        other => bail!("Images with color type {:?} are unsupported", other),
+    };
+
+    Ok((info.width as usize, buf))
+}
+
+/// Returns (width, rgb data) for the given JPEG image reader
+#[cfg(feature = "jpeg")]
+pub fn load_jpeg_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Jpeg)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for the given 24-bit or 32-bit BMP image reader
+#[cfg(feature = "bmp")]
+pub fn load_bmp_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Bmp)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for the first frame of the given GIF image reader,
+/// with its palette resolved and transparency dropped
+#[cfg(feature = "gif")]
+pub fn load_gif_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Gif)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for the given Netpbm image reader, such as a binary P5 (gray)
+/// or P6 (rgb) file
+#[cfg(feature = "pnm")]
+pub fn load_pnm_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Pnm)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for a stream of raw interleaved 8-bit RGB samples of the given
+/// size, with no header to decode
+pub fn load_raw_rgb<R: Read>(mut r: R, width: usize, height: usize) -> Result<(usize, Vec<u8>)> {
+    let mut buf = vec![];
+    r.read_to_end(&mut buf).context("Reading raw pixels")?;
+    check_raw_len(buf.len(), width, height)?;
+    Ok((width, buf))
+}
+
+/// Returns (width, rgba data) for a stream of raw interleaved 8-bit RGB samples of the given
+/// size, with an alpha of 255
+pub fn load_raw_rgba<R: Read>(r: R, width: usize, height: usize) -> Result<(usize, Vec<u8>)> {
+    let (width, rgb) = load_raw_rgb(r, width, height)?;
+    Ok((width, rgb_to_rgba(&rgb)))
+}
+
+/// Like [`load_raw_rgba`], for an opened file. Mapped files are converted straight from
+/// the map, without reading them into a buffer first
+pub fn load_raw_input_rgba(
+    input: InputFile,
+    width: usize,
+    height: usize,
+) -> Result<(usize, Vec<u8>)> {
+    match input {
+        InputFile::Mapped(map) => {
+            let rgb = &map.get_ref()[..];
+            check_raw_len(rgb.len(), width, height)?;
+            Ok((width, rgb_to_rgba(rgb)))
+        }
+        InputFile::Buffered(reader) => load_raw_rgba(reader, width, height),
+    }
+}
+
+/// Fails unless `len` bytes is exactly enough for raw RGB samples of the given size
+fn check_raw_len(len: usize, width: usize, height: usize) -> Result<()> {
+    if len != width * height * 3 {
+        bail!(
+            "Raw image of {}x{} pixels should be {} bytes, but is {}",
+            width,
+            height,
+            width * height * 3,
+            len
+        );
+    }
+    Ok(())
+}
+
+/// Adds an alpha of 255 to rgb data
+fn rgb_to_rgba(rgb: &[u8]) -> Vec<u8> {
+    rgb.chunks_exact(3)
+        .flat_map(|px| [px[0], px[1], px[2], 255])
+        .collect()
+}
+
+/// Returns (width, rgb data) for the given 24-bit or 32-bit TGA image reader, either
+/// uncompressed or run-length encoded
+#[cfg(feature = "tga")]
+pub fn load_tga_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Tga)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for the given WebP image reader
+#[cfg(feature = "webp")]
+pub fn load_webp_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::WebP)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Returns (width, rgb data) for the given TIFF image reader. Only 8-bit grayscale and RGB
+/// images, with or without alpha, are supported
+#[cfg(feature = "tiff")]
+pub fn load_tiff_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
+    let (width, rgba) = load_with_image_crate(r, ImageFormat::Tiff)?;
+    Ok((width, rgba_to_rgb(&rgba)))
+}
+
+/// Drops the alpha channel from rgba data
+pub fn rgba_to_rgb(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| [px[0], px[1], px[2]])
+        .collect()
+}
+
+/// Blends rgba data over the background color by its alpha, returning opaque rgb data
+pub fn composite_over(rgba: &[u8], background: [u8; 3]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| {
+            let alpha = px[3] as u32;
+            let blend = |channel: usize| {
+                let mixed = px[channel] as u32 * alpha + background[channel] as u32 * (255 - alpha);
+                ((mixed + 127) / 255) as u8
+            };
+            [blend(0), blend(1), blend(2)]
+        })
+        .collect()
+}
+
+/// Reads the header of the image with the `image` crate, without decoding the rest
+fn info_with_image_crate<R: BufRead + Seek>(r: R, format: ImageFormat) -> Result<ImageInfo> {
+    let decoder = image::ImageReader::with_format(r, format)
+        .into_decoder()
+        .with_context(|| format!("Decoding {:?}", format))?;
+    let (width, height) = decoder.dimensions();
+    let color_type = decoder.original_color_type();
+
+    Ok(ImageInfo {
+        width: width as usize,
+        height: height as usize,
+        color_type: format!("{:?}", color_type),
+        bit_depth: color_type.bits_per_pixel() as u8 / color_type.channel_count().max(1),
+    })
+}
+
+/// Decodes the whole stream with the `image` crate and converts it to 8-bit RGBA
+#[cfg(any(
+    feature = "jpeg",
+    feature = "bmp",
+    feature = "gif",
+    feature = "pnm",
+    feature = "tga",
+    feature = "webp",
+    feature = "tiff"
+))]
+fn load_with_image_crate<R: Read>(mut r: R, format: ImageFormat) -> Result<(usize, Vec<u8>)> {
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes).context("Reading image")?;
+    decode_with_image_crate(Cursor::new(&bytes[..]), format)
+}
+
+/// Decodes the image with the `image` crate and converts it to 8-bit RGBA
+fn decode_with_image_crate<R: BufRead + Seek>(
+    r: R,
+    format: ImageFormat,
+) -> Result<(usize, Vec<u8>)> {
+    let image = image::ImageReader::with_format(r, format)
+        .decode()
+        .with_context(|| format!("Decoding {:?}", format))?;
+
+    // Scans come in all sorts of depths and color spaces, which would be converted silently
+    let color = image.color();
+    if format == ImageFormat::Tiff
+        && !matches!(
+            color,
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+        )
+    {
+        bail!(
+            "{:?} TIFF images are not supported (expected 8-bit grayscale or RGB)",
+            color
+        );
+    }
+    let image = image.into_rgba8();
+
+    Ok((image.width() as usize, image.into_raw()))
+}
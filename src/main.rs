@@ -1,156 +1,1687 @@
 use anyhow::{bail, Context, Result};
-use std::io::Read;
+use clap::builder::BoolishValueParser;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches, Parser};
+use dont_tell_avali::{
+    build_animated_document, build_document_rgba_with_stats, image_info, is_supported_image,
+    load_image_rgba, load_palette, load_png_rgba, load_raw_input_rgba, load_raw_rgba,
+    open_input_file, parse_hex_color, png_info, render_to_png, rows_within_tile_count,
+    stream_document_with_stats, tile_grid_size, tiled_size, triangle_cells_rgba,
+    triangle_grid_size, view_box_size, write_csv, write_dxf, write_eps, write_json,
+    AdaptiveOptions, AverageSpace, ColorOptions, CropRect, DensityMap, DocumentMetadata,
+    DropShadow, ImageInfo, Orientation, PaletteSpace, PhysicalWidth, RenderOptions, RenderStats,
+    SampleMode, SamplePoint, Shape, TileElement, TileStyle, Triangle, TriangleGrid, Unit,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use svg::node::element::{path::Data as SvgData, Path as SvgPath};
-use svg::Node;
+use std::str::FromStr;
+use std::time::Instant;
 
-fn main() -> Result<()> {
-    // Arg parsing
-    let mut args = std::env::args();
-    let program_name = args.next().unwrap();
-    let usage = || {
-        format!(
-            "Usage: {} <image path> <# vertical triangles (30)> <triangle height (0.1)> <out path>",
-            program_name
-        )
+/// The kinds of file the mosaic can be written as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Svg,
+    Dxf,
+    Eps,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    /// Extension of the file written when no output path is given
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Dxf => "dxf",
+            OutputFormat::Eps => "eps",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "svg" => Ok(OutputFormat::Svg),
+            "dxf" => Ok(OutputFormat::Dxf),
+            "eps" => Ok(OutputFormat::Eps),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            other => bail!(
+                "Unknown output format {:?} (expected svg, dxf, eps, json or csv)",
+                other
+            ),
+        }
+    }
+}
+
+/// Bundles of settings giving the mosaic a particular look
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    /// Few flat colors with dark outlines around each region, like a comic
+    Cel,
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cel" => Ok(Preset::Cel),
+            other => bail!("Unknown preset {:?} (expected cel)", other),
+        }
+    }
+}
+
+/// Outline width of every tile unless --stroke-width says otherwise
+const DEFAULT_STROKE_WIDTH: f32 = 0.001;
+
+/// Tiles an image with colored triangles and saves the result as an SVG
+#[derive(Parser)]
+#[command(version, about, args_override_self = true)]
+struct Cli {
+    /// Read settings from this TOML (or .json) file, with keys named like the long flags,
+    /// such as `vertical = 60` or `merge = true`. Flags on the command line take precedence,
+    /// and switches accept a value to turn one off, as in `--merge=false`
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Path to the source image, or - to read a PNG (or --raw pixels) from stdin.
+    /// Given a directory, or a glob pattern like frames/*.png, every image in it is rendered
+    /// into the --output directory
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+
+    /// Render an animated SVG from the images matching this glob pattern, in name order,
+    /// with every triangle stepping through its color in each frame
+    #[arg(
+        long,
+        value_name = "GLOB",
+        conflicts_with_all = ["input", "raw", "stream", "bench", "png", "adaptive", "density_map"]
+    )]
+    frames: Option<String>,
+
+    /// Frames per second of the --frames animation
+    #[arg(long, value_name = "N", default_value_t = 12.)]
+    fps: f32,
+
+    /// Read the input as raw interleaved 8-bit RGB samples of a WxH image, without decoding
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    raw: Option<(usize, usize)>,
+
+    /// Number of triangles stacked vertically
+    #[arg(short, long, value_name = "N", default_value_t = 30)]
+    vertical: usize,
+
+    /// Only tile the W x H rectangle of source pixels whose top left corner is at (X, Y)
+    #[arg(long, num_args = 4, value_names = ["X", "Y", "W", "H"])]
+    crop: Option<Vec<usize>>,
+
+    /// Blur the source image (after cropping) by this standard deviation, in pixels, before
+    /// resizing and tiling it. Smooths out noise and aliasing on coarse grids
+    #[arg(long, value_name = "SIGMA", default_value_t = 0.)]
+    blur: f32,
+
+    /// Rescale the source image (after cropping) to WxH pixels before tiling
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    resize: Option<(usize, usize)>,
+
+    /// Tile the image next to its reflections across the right and bottom edges, so the mosaic
+    /// repeats seamlessly. Use an even number of rows for the triangles to line up as well
+    #[arg(long)]
+    mirror: bool,
+
+    /// Sample the image mirrored left to right
+    #[arg(long)]
+    flip_h: bool,
+
+    /// Sample the image mirrored top to bottom
+    #[arg(long)]
+    flip_v: bool,
+
+    /// Lower the number of rows, if needed, so that the grid has at most N tiles.
+    /// Triangles split by --adaptive or --density-map are not counted
+    #[arg(long, value_name = "N")]
+    max_triangles: Option<usize>,
+
+    /// Lower the number of rows, if needed, so that each is at least this many pixels tall
+    /// in the PNG preview, or in the source image without one
+    #[arg(long, value_name = "PIXELS")]
+    min_height: Option<f32>,
+
+    /// Number of triangles across each row, not counting one more that pokes half out past
+    /// the right edge. Derived from the image's aspect ratio if omitted
+    #[arg(long, value_name = "N")]
+    horizontal: Option<usize>,
+
+    /// Derive the number of triangles across each row the way older versions did,
+    /// which distorts the aspect ratio of small grids
+    #[arg(long)]
+    no_aspect_correct: bool,
+
+    /// Height of each triangle in SVG units
+    #[arg(long, value_name = "F", default_value_t = 0.1)]
+    height: f32,
+
+    /// Print the image's size, color type and bit depth, and the triangle grid, then exit
+    #[arg(long)]
+    info: bool,
+
+    /// Time generating the mosaic and print how many tiles were made per second, without
+    /// writing any output
+    #[arg(long, conflicts_with_all = ["info", "stream", "png"])]
+    bench: bool,
+
+    /// Generate the mosaic and print how many tiles and colors it has and how big the file
+    /// would be, without writing anything
+    #[arg(long, conflicts_with_all = ["info", "bench", "png"])]
+    dry_run: bool,
+
+    /// Don't print a summary of the render, or notes on adjusted settings, to stderr
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Path to write the output to, or - for stdout. Defaults to out.svg, or out.dxf for DXF.
+    /// With several inputs, the directory to write them to, defaulting to the current one
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Title of the SVG, recorded next to the source file and grid size
+    #[arg(long, value_name = "TEXT")]
+    title: Option<String>,
+
+    /// Physical width of the SVG or EPS, in --unit. The height follows the mosaic's aspect
+    /// ratio. EPS output is printed an inch wide per SVG unit without it
+    #[arg(long, value_name = "VAL")]
+    width: Option<f32>,
+
+    /// Unit of --width: mm, in or px
+    #[arg(long, default_value = "mm")]
+    unit: Unit,
+
+    /// Kind of file to write: svg, dxf for the triangle outlines grouped into one layer per
+    /// color, eps for a PostScript page of filled triangles, json for the list of triangles
+    /// and their colors, or csv for a table of triangle centroids and colors
+    #[arg(long, default_value = "svg")]
+    format: OutputFormat,
+
+    /// Write the SVG while the rows are sampled instead of building it in memory first,
+    /// for grids too large to hold at once. Only plain horizontal triangle grids can be streamed
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "png", "merge", "classes", "defs", "layers_by_color", "colors", "dither",
+            "region_borders"
+        ]
+    )]
+    stream: bool,
+
+    /// Also rasterize the mosaic to a PNG preview at this path. With several inputs,
+    /// each preview is named after its image instead
+    #[arg(long, value_name = "PATH")]
+    png: Option<PathBuf>,
+
+    /// Width of the PNG preview in pixels. The height follows the mosaic's aspect ratio
+    #[arg(long, value_name = "PX", default_value_t = 1024)]
+    png_width: u32,
+
+    /// Kind of tile to divide the image into: triangle, hex, square or diamond.
+    /// Hexagon and diamond rows are spaced by, and squares are as tall as, the triangle height
+    #[arg(long, default_value = "triangle")]
+    shape: Shape,
+
+    /// Which way the rows of triangles run: horizontal, or vertical for triangles pointing
+    /// left and right. Vertical grids count columns with -v instead of rows
+    #[arg(long, default_value = "horizontal")]
+    orientation: Orientation,
+
+    /// Shift every other row of triangles right by this fraction of a triangle's base,
+    /// e.g. 0.5, widening the mosaic to fit
+    #[arg(long, value_name = "F", default_value_t = 0.)]
+    stagger: f32,
+
+    /// How to pick each triangle's color: nearest, bilinear, average or median
+    #[arg(long, value_name = "MODE", default_value = "nearest")]
+    sample: SampleMode,
+
+    /// Where within each triangle to sample its color: corner or centroid
+    #[arg(long, value_name = "POINT", default_value = "corner")]
+    sample_at: SamplePoint,
+
+    /// Split triangles in four, again and again, wherever the color variance of the pixels
+    /// under them is above the threshold (400 if omitted)
+    #[arg(
+        long,
+        value_name = "THRESHOLD",
+        num_args = 0..=1,
+        default_missing_value = "400",
+        conflicts_with = "defs"
+    )]
+    adaptive: Option<f32>,
+
+    /// Split triangles in four, again and again, the brighter this grayscale image is under
+    /// them: not at all where it is black, and --max-depth times where it is white.
+    /// The map is stretched over the image as it is tiled, after cropping and resizing
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        conflicts_with_all = ["defs", "adaptive"]
+    )]
+    density_map: Option<PathBuf>,
+
+    /// How many times --adaptive or --density-map may split a triangle of the regular grid
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u8).range(1..=8)
+    )]
+    max_depth: u8,
+
+    /// Average an NxN grid of source pixels within each triangle, overriding --sample.
+    /// Combine with --linear-average for the smoothest results
+    #[arg(long, value_name = "N")]
+    supersample: Option<usize>,
+
+    /// Average colors in linear light rather than directly on sRGB values
+    #[arg(long)]
+    linear_average: bool,
+
+    /// Average colors after applying this gamma, rather than directly on the stored values
+    /// (2.2 if omitted)
+    #[arg(
+        long,
+        value_name = "GAMMA",
+        num_args = 0..=1,
+        default_missing_value = "2.2",
+        conflicts_with = "linear_average"
+    )]
+    gamma: Option<f32>,
+
+    /// Color space to average colors in: srgb, linear or oklab
+    #[arg(
+        long,
+        value_name = "SPACE",
+        conflicts_with_all = ["linear_average", "gamma"]
+    )]
+    average_space: Option<AverageSpace>,
+
+    /// SVG element to draw each triangle with: path or polygon
+    #[arg(
+        long,
+        default_value = "path",
+        conflicts_with_all = ["merge", "classes", "defs", "corner_radius"]
+    )]
+    element: TileElement,
+
+    /// Start from a bundle of settings for a particular look. cel posterizes the colors
+    /// to 2 bits and outlines each region of merged tiles in dark gray. Flags given alongside
+    /// it override its color depth and outline
+    #[arg(long, value_name = "NAME", conflicts_with = "element")]
+    preset: Option<Preset>,
+
+    /// Merge adjacent triangles of the same color into shared paths, shrinking the output
+    #[arg(long)]
+    merge: bool,
+
+    /// Shade each triangle with a gradient through the colors under its corners, for smooth
+    /// rather than faceted output
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "merge", "classes", "defs", "layers_by_color", "posterize", "colors", "palette",
+            "dither", "frames"
+        ]
+    )]
+    gradient: bool,
+
+    /// Define one CSS class per distinct color instead of repeating fills on every triangle
+    #[arg(long)]
+    classes: bool,
+
+    /// Define the triangle outlines once and place each triangle with a `<use>` reference
+    #[arg(long, conflicts_with_all = ["merge", "classes"])]
+    defs: bool,
+
+    /// Put the tiles of each color in their own group, with an id like `color-FF8800`,
+    /// so they can be toggled or cut in separate passes
+    #[arg(long, conflicts_with_all = ["classes", "defs"])]
+    layers_by_color: bool,
+
+    /// Outline color of every tile, as any SVG color
+    #[arg(long, value_name = "COLOR", default_value = "none")]
+    stroke: String,
+
+    /// Outline width of every tile in SVG units
+    #[arg(long, value_name = "F", default_value_t = DEFAULT_STROKE_WIDTH)]
+    stroke_width: f32,
+
+    /// Draw a line of this color, --stroke-width wide, along the borders between differently
+    /// colored triangles, outlining each region of one color
+    #[arg(long, value_name = "COLOR")]
+    region_borders: Option<String>,
+
+    /// Only draw the outline of every tile, leaving it unfilled, as for a coloring book.
+    /// Outlines are black unless --stroke says otherwise
+    #[arg(long)]
+    no_fill: bool,
+
+    /// Width of the empty space left between neighboring tiles, in SVG units
+    #[arg(long, value_name = "F", default_value_t = 0.)]
+    gap: f32,
+
+    /// Randomly move the corners of each triangle by up to this far along each axis,
+    /// in SVG units, for a hand-drawn look
+    #[arg(long, value_name = "F", default_value_t = 0., conflicts_with = "defs")]
+    jitter: f32,
+
+    /// Seed for --jitter and --randomize-colors. The same seed always produces the same file
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Round the corners of each triangle with arcs of this radius, in SVG units
+    #[arg(long = "round", value_name = "RADIUS", default_value_t = 0.)]
+    corner_radius: f32,
+
+    /// Number of decimals to round every coordinate to
+    #[arg(long, value_name = "DIGITS", default_value_t = 4)]
+    precision: u8,
+
+    /// Leave this much blank space around the SVG mosaic on every side, in SVG units
+    #[arg(long, value_name = "F", default_value_t = 0.)]
+    margin: f32,
+
+    /// Draw a rectangle of this color behind the mosaic
+    #[arg(long, value_name = "COLOR")]
+    background: Option<String>,
+
+    /// Cast a drop shadow from the whole mosaic, for a layered paper-cut look on screen
+    #[arg(long)]
+    shadow: bool,
+
+    /// How far right and down the shadow falls, in SVG units (a tenth of --height if omitted)
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["DX", "DY"],
+        allow_negative_numbers = true
+    )]
+    shadow_offset: Option<Vec<f32>>,
+
+    /// How far the shadow is blurred, as a standard deviation in SVG units
+    /// (a tenth of --height if omitted)
+    #[arg(long, value_name = "F")]
+    shadow_blur: Option<f32>,
+
+    /// Color of the shadow, as any SVG color
+    #[arg(long, value_name = "COLOR", default_value = "black")]
+    shadow_color: String,
+
+    /// Blend the image over this hex color by its transparency before tiling it, rather than
+    /// ignoring the transparency, which leaves dark fringes around anti-aliased edges
+    #[arg(
+        long,
+        value_name = "COLOR",
+        conflicts_with_all = ["keep_alpha", "skip_transparent"]
+    )]
+    composite_over: Option<String>,
+
+    /// Carry the image's transparency through as the opacity of each tile
+    #[arg(long)]
+    keep_alpha: bool,
+
+    /// Leave out tiles whose alpha is below the given value (1, so only fully transparent, if omitted)
+    #[arg(long, value_name = "ALPHA", num_args = 0..=1, default_missing_value = "1")]
+    skip_transparent: Option<u8>,
+
+    /// Leave out tiles whose color is close to this one, to drop a solid background
+    #[arg(long, value_name = "COLOR")]
+    chroma_key: Option<String>,
+
+    /// How far from --chroma-key a color may be and still be left out, in RGB units
+    /// (or ΔE with --palette-space lab)
+    #[arg(long, value_name = "DISTANCE", default_value_t = 32.)]
+    chroma_tolerance: f32,
+
+    /// Only draw tiles lying on edges in the image, where the Sobel gradient of the
+    /// luminance reaches the threshold (128 if omitted), leaving flat areas empty
+    #[arg(
+        long,
+        value_name = "THRESHOLD",
+        num_args = 0..=1,
+        default_missing_value = "128"
+    )]
+    edges: Option<f32>,
+
+    /// Draw every tile in one ink color, with an opacity following how dark the image is
+    /// under it
+    #[arg(long)]
+    opacity_from_luma: bool,
+
+    /// Color of the ink drawn with --opacity-from-luma
+    #[arg(long, value_name = "COLOR", default_value = "#000")]
+    ink: String,
+
+    /// Render every tile in the gray of equal luminance
+    #[arg(long)]
+    grayscale: bool,
+
+    /// Rotate the hue of every color by this many degrees
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.,
+        allow_negative_numbers = true
+    )]
+    hue: f32,
+
+    /// Factor the saturation of every color is scaled by
+    #[arg(long, value_name = "F", default_value_t = 1.)]
+    saturation: f32,
+
+    /// Offset added to every color channel, from -255 to 255
+    #[arg(
+        long,
+        value_name = "F",
+        default_value_t = 0.,
+        allow_negative_numbers = true
+    )]
+    brightness: f32,
+
+    /// Factor every color channel is scaled by around the midpoint
+    #[arg(long, value_name = "F", default_value_t = 1.)]
+    contrast: f32,
+
+    /// Render every tile in its negative color
+    #[arg(long)]
+    invert: bool,
+
+    /// Render every tile white if its luminance reaches this level (0 to 255), or black otherwise
+    #[arg(long, value_name = "LEVEL")]
+    threshold: Option<u8>,
+
+    /// Reduce each color channel to this many bits, from 1 to 8
+    #[arg(long, value_name = "BITS", value_parser = clap::value_parser!(u8).range(1..=8))]
+    posterize: Option<u8>,
+
+    /// Reduce the mosaic to at most N colors with k-means clustering
+    #[arg(long, value_name = "N")]
+    colors: Option<usize>,
+
+    /// Snap every tile to the closest color listed in this file, one hex color per line
+    #[arg(long, value_name = "FILE", conflicts_with = "colors")]
+    palette: Option<PathBuf>,
+
+    /// Color space to match palette colors and --chroma-key in: rgb or lab
+    #[arg(long, value_name = "SPACE", default_value = "rgb")]
+    palette_space: PaletteSpace,
+
+    /// Spread the error from --posterize, --colors and --palette over neighboring tiles
+    #[arg(long)]
+    dither: bool,
+
+    /// Fill each tile with a random color from --palette instead of the image's, for a
+    /// confetti look. Only tiles over dark, opaque parts of the image are drawn
+    #[arg(long, conflicts_with = "dither")]
+    randomize_colors: bool,
+}
+
+/// Declares [`Settings`], holding an optional value for each of the listed [`Cli`] fields
+macro_rules! settings {
+    ($($(#[$attr:meta])* $field:ident: $ty:ty,)*) => {
+        /// Settings read from a --config file, each keyed like the long flag it stands in for
+        #[derive(Default, Deserialize)]
+        #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+        struct Settings {
+            $($(#[$attr])* $field: Option<$ty>,)*
+        }
+
+        impl Settings {
+            /// Copies each setting into `cli` unless `matches` shows its flag was given on the
+            /// command line. Returns the ids of the flags whose values changed
+            fn merge_under(self, cli: &mut Cli, matches: &ArgMatches) -> Vec<&'static str> {
+                let mut merged = vec![];
+                $(
+                    if let Some(value) = self.$field {
+                        let id = stringify!($field);
+                        if matches.value_source(id) != Some(ValueSource::CommandLine)
+                            && cli.$field != value
+                        {
+                            cli.$field = value;
+                            merged.push(id);
+                        }
+                    }
+                )*
+                merged
+            }
+        }
     };
+}
+
+settings! {
+    input: Option<PathBuf>,
+    frames: Option<String>,
+    fps: f32,
+    #[serde(deserialize_with = "size_setting")]
+    raw: Option<(usize, usize)>,
+    vertical: usize,
+    crop: Option<Vec<usize>>,
+    blur: f32,
+    #[serde(deserialize_with = "size_setting")]
+    resize: Option<(usize, usize)>,
+    mirror: bool,
+    flip_h: bool,
+    flip_v: bool,
+    max_triangles: Option<usize>,
+    min_height: Option<f32>,
+    horizontal: Option<usize>,
+    no_aspect_correct: bool,
+    height: f32,
+    info: bool,
+    bench: bool,
+    dry_run: bool,
+    quiet: bool,
+    output: Option<PathBuf>,
+    title: Option<String>,
+    width: Option<f32>,
+    #[serde(deserialize_with = "parsed_setting")]
+    unit: Unit,
+    #[serde(deserialize_with = "parsed_setting")]
+    format: OutputFormat,
+    stream: bool,
+    png: Option<PathBuf>,
+    png_width: u32,
+    #[serde(deserialize_with = "parsed_setting")]
+    shape: Shape,
+    #[serde(deserialize_with = "parsed_setting")]
+    orientation: Orientation,
+    stagger: f32,
+    #[serde(deserialize_with = "parsed_setting")]
+    sample: SampleMode,
+    #[serde(deserialize_with = "parsed_setting")]
+    sample_at: SamplePoint,
+    adaptive: Option<f32>,
+    density_map: Option<PathBuf>,
+    max_depth: u8,
+    supersample: Option<usize>,
+    linear_average: bool,
+    gamma: Option<f32>,
+    #[serde(deserialize_with = "optional_parsed_setting")]
+    average_space: Option<AverageSpace>,
+    #[serde(deserialize_with = "parsed_setting")]
+    element: TileElement,
+    #[serde(deserialize_with = "optional_parsed_setting")]
+    preset: Option<Preset>,
+    merge: bool,
+    gradient: bool,
+    classes: bool,
+    defs: bool,
+    layers_by_color: bool,
+    stroke: String,
+    stroke_width: f32,
+    region_borders: Option<String>,
+    no_fill: bool,
+    gap: f32,
+    jitter: f32,
+    seed: u64,
+    #[serde(rename = "round")]
+    corner_radius: f32,
+    precision: u8,
+    margin: f32,
+    background: Option<String>,
+    shadow: bool,
+    shadow_offset: Option<Vec<f32>>,
+    shadow_blur: Option<f32>,
+    shadow_color: String,
+    composite_over: Option<String>,
+    keep_alpha: bool,
+    skip_transparent: Option<u8>,
+    chroma_key: Option<String>,
+    chroma_tolerance: f32,
+    edges: Option<f32>,
+    opacity_from_luma: bool,
+    ink: String,
+    grayscale: bool,
+    hue: f32,
+    saturation: f32,
+    brightness: f32,
+    contrast: f32,
+    invert: bool,
+    threshold: Option<u8>,
+    posterize: Option<u8>,
+    colors: Option<usize>,
+    palette: Option<PathBuf>,
+    #[serde(deserialize_with = "parsed_setting")]
+    palette_space: PaletteSpace,
+    dither: bool,
+    randomize_colors: bool,
+}
+
+/// Flags that only mean something alongside another, or one of several others. These are
+/// checked once the --config file is merged in, so either side may come from it
+const REQUIREMENTS: &[(&str, &[&str])] = &[
+    ("fps", &["frames"]),
+    ("unit", &["width"]),
+    ("max_depth", &["adaptive", "density_map"]),
+    ("shadow_offset", &["shadow"]),
+    ("shadow_blur", &["shadow"]),
+    ("shadow_color", &["shadow"]),
+    ("chroma_tolerance", &["chroma_key"]),
+    ("ink", &["opacity_from_luma"]),
+    ("randomize_colors", &["palette"]),
+];
+
+/// Settings for a single render
+#[derive(Clone)]
+struct Config {
+    image_path: PathBuf,
+    raw: Option<(usize, usize)>,
+    frames: Option<String>,
+    fps: f32,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    max_triangles: Option<usize>,
+    min_height: Option<f32>,
+    out_path: PathBuf,
+    title: Option<String>,
+    info: bool,
+    bench: bool,
+    dry_run: bool,
+    quiet: bool,
+    format: OutputFormat,
+    stream: bool,
+    png_path: Option<PathBuf>,
+    png_width: u32,
+    render: RenderOptions,
+}
+
+impl Config {
+    /// Parses and validates the command line arguments
+    fn from_args() -> Result<Config> {
+        let mut command = switches_take_values(Cli::command());
+        let matches = command.get_matches_mut();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        let mut merged = match cli.config.clone() {
+            Some(path) => read_settings(&path)
+                .with_context(|| format!("Reading settings from {}", path.display()))?
+                .merge_under(&mut cli, &matches),
+            None => vec![],
+        };
+        // The preset's flags are checked against the rest just like the file's
+        if cli.preset == Some(Preset::Cel) {
+            merged.extend(apply_cel_preset(&mut cli));
+        }
+        check_flags(&command, &matches, &merged)?;
+
+        if cli.input.is_none() && cli.frames.is_none() {
+            bail!("An --input image is required, unless rendering --frames");
+        }
+
+        if cli.vertical == 0 {
+            bail!("# of vertical triangles must be greater than zero");
+        }
+
+        if cli
+            .width
+            .is_some_and(|width| width <= 0.0 || width.is_nan())
+        {
+            bail!("Width must be greater than zero");
+        }
+
+        if cli
+            .min_height
+            .is_some_and(|height| height <= 0.0 || height.is_nan())
+        {
+            bail!("Minimum height must be greater than zero");
+        }
+
+        if cli.max_triangles == Some(0) {
+            bail!("Maximum # of triangles must be greater than zero");
+        }
+
+        if cli.horizontal == Some(0) {
+            bail!("# of horizontal triangles must be greater than zero");
+        }
+
+        if cli.height <= 0.0 || cli.height.is_nan() {
+            bail!("Triangle height must be greater than zero");
+        }
+
+        if cli.colors == Some(0) {
+            bail!("# of colors must be greater than zero");
+        }
+
+        if cli
+            .gamma
+            .is_some_and(|gamma| gamma <= 0.0 || gamma.is_nan())
+        {
+            bail!("Gamma must be greater than zero");
+        }
+
+        if cli.jitter < 0.0 || cli.jitter.is_nan() {
+            bail!("Jitter must not be negative");
+        }
+
+        if cli.corner_radius < 0.0 || cli.corner_radius.is_nan() {
+            bail!("Corner radius must not be negative");
+        }
+
+        if cli
+            .adaptive
+            .is_some_and(|threshold| threshold < 0.0 || threshold.is_nan())
+        {
+            bail!("Adaptive threshold must not be negative");
+        }
+
+        if cli.stagger < 0.0 || cli.stagger.is_nan() {
+            bail!("Stagger must not be negative");
+        }
+
+        if cli
+            .shadow_blur
+            .is_some_and(|blur| blur < 0.0 || blur.is_nan())
+        {
+            bail!("Shadow blur must not be negative");
+        }
+
+        if cli.blur < 0.0 || cli.blur.is_nan() {
+            bail!("Blur must not be negative");
+        }
+
+        if cli.margin < 0.0 || cli.margin.is_nan() {
+            bail!("Margin must not be negative");
+        }
+
+        if cli.chroma_tolerance < 0.0 || cli.chroma_tolerance.is_nan() {
+            bail!("Chroma key tolerance must not be negative");
+        }
+
+        if cli.fps <= 0.0 || cli.fps.is_nan() {
+            bail!("Frame rate must be greater than zero");
+        }
+
+        if cli.frames.is_some() && cli.format != OutputFormat::Svg {
+            bail!("Only SVG output can be animated");
+        }
 
-    let image_path: PathBuf = args.next().with_context(usage)?.into();
+        if !(1..=8).contains(&cli.max_depth) {
+            bail!("Maximum depth must be between 1 and 8");
+        }
 
-    let n_vertical_tris: usize = args
-        .next()
-        .unwrap_or("30".to_string())
-        .parse()
-        .context("# of vertical triangles")?;
+        if cli.posterize.is_some_and(|bits| !(1..=8).contains(&bits)) {
+            bail!("Posterize bits must be between 1 and 8");
+        }
 
-    let triangle_height: f32 = args
-        .next()
-        .unwrap_or("0.1".to_string())
-        .parse()
-        .context("Triangle height")?;
+        if cli.supersample == Some(0) {
+            bail!("Supersample grid size must be greater than zero");
+        }
 
-    let svg_path = args
-        .next()
-        .unwrap_or("out.svg".to_string());
+        if cli.format != OutputFormat::Svg {
+            if cli.shape != Shape::Triangle {
+                bail!("Only SVG output supports tiles other than triangles");
+            }
+            if cli.orientation != Orientation::Horizontal {
+                bail!("Only SVG output supports vertical grids");
+            }
+            if cli.png.is_some() {
+                bail!("A PNG preview can only be rendered alongside SVG output");
+            }
+        }
+
+        let (format, unit, max_depth) = (cli.format, cli.unit, cli.max_depth as usize);
+        // Shadows fall a tenth of a triangle away unless told otherwise
+        let shadow = cli.shadow.then(|| {
+            let (dx, dy) = match cli.shadow_offset.as_deref() {
+                Some(&[dx, dy]) => (dx, dy),
+                _ => (cli.height / 10., cli.height / 10.),
+            };
+            DropShadow {
+                dx,
+                dy,
+                blur: cli.shadow_blur.unwrap_or(cli.height / 10.),
+                color: cli.shadow_color.clone(),
+            }
+        });
+        let batch = cli
+            .input
+            .as_deref()
+            .is_some_and(|input| input.is_dir() || is_glob_pattern(input));
+        Ok(Config {
+            image_path: cli.input.unwrap_or_default(),
+            raw: cli.raw,
+            frames: cli.frames,
+            fps: cli.fps,
+            n_vertical_tris: cli.vertical,
+            max_triangles: cli.max_triangles,
+            min_height: cli.min_height,
+            triangle_height: cli.height,
+            out_path: cli.output.unwrap_or_else(|| {
+                if batch {
+                    ".".into()
+                } else {
+                    format!("out.{}", format.extension()).into()
+                }
+            }),
+            title: cli.title,
+            info: cli.info,
+            bench: cli.bench,
+            dry_run: cli.dry_run,
+            quiet: cli.quiet,
+            format,
+            stream: cli.stream,
+            png_path: cli.png,
+            png_width: cli.png_width,
+            render: RenderOptions {
+                style: TileStyle {
+                    stroke: if cli.no_fill && cli.stroke == "none" {
+                        "black".to_string()
+                    } else {
+                        cli.stroke
+                    },
+                    outline_only: cli.no_fill,
+                    stroke_width: cli.stroke_width,
+                    gap: cli.gap,
+                    precision: Some(cli.precision),
+                    jitter: cli.jitter,
+                    seed: cli.seed,
+                    corner_radius: cli.corner_radius,
+                },
+                crop: cli.crop.map(|crop| CropRect {
+                    x: crop[0],
+                    y: crop[1],
+                    width: crop[2],
+                    height: crop[3],
+                }),
+                blur: cli.blur,
+                resize: cli.resize,
+                mirror: cli.mirror,
+                flip_h: cli.flip_h,
+                flip_v: cli.flip_v,
+                horizontal: cli.horizontal,
+                legacy_aspect: cli.no_aspect_correct,
+                shape: cli.shape,
+                orientation: cli.orientation,
+                stagger: cli.stagger,
+                sample: cli.sample,
+                sample_at: cli.sample_at,
+                adaptive: cli.adaptive.map(|threshold| AdaptiveOptions {
+                    threshold,
+                    max_depth,
+                }),
+                density_map: cli
+                    .density_map
+                    .map(|path| DensityMap::load(path, max_depth))
+                    .transpose()?,
+                supersample: cli.supersample,
+                average_space: match (cli.average_space, cli.gamma) {
+                    (Some(space), _) => space,
+                    (None, Some(gamma)) => AverageSpace::Gamma(gamma),
+                    (None, None) if cli.linear_average => AverageSpace::Linear,
+                    (None, None) => AverageSpace::Srgb,
+                },
+                element: cli.element,
+                gradient: cli.gradient,
+                merge: cli.merge,
+                classes: cli.classes,
+                defs: cli.defs,
+                layers_by_color: cli.layers_by_color,
+                physical_width: cli.width.map(|value| PhysicalWidth { value, unit }),
+                region_borders: cli.region_borders,
+                margin: cli.margin,
+                metadata: None,
+                background: cli.background,
+                shadow,
+                composite_over: cli
+                    .composite_over
+                    .as_deref()
+                    .map(parse_hex_color)
+                    .transpose()?,
+                keep_alpha: cli.keep_alpha,
+                skip_transparent: cli.skip_transparent,
+                chroma_key: cli.chroma_key.as_deref().map(parse_hex_color).transpose()?,
+                chroma_tolerance: cli.chroma_tolerance,
+                edges: cli.edges,
+                ink: if cli.opacity_from_luma {
+                    Some(parse_hex_color(&cli.ink)?)
+                } else {
+                    None
+                },
+                progress: None,
+                posterize: cli.posterize,
+                colors: cli.colors,
+                palette: cli.palette.map(load_palette).transpose()?,
+                palette_space: cli.palette_space,
+                randomize_colors: cli.randomize_colors,
+                dither: cli.dither,
+                color: ColorOptions {
+                    brightness: cli.brightness,
+                    contrast: cli.contrast,
+                    hue: cli.hue,
+                    saturation: cli.saturation,
+                    grayscale: cli.grayscale,
+                    threshold: cli.threshold,
+                    invert: cli.invert,
+                },
+            },
+        })
+    }
+}
+
+fn main() -> Result<()> {
+    let config = Config::from_args()?;
+
+    if let Some(pattern) = &config.frames {
+        return render_animation(pattern, config.clone());
+    }
+
+    if config.image_path.is_dir() {
+        return process_batch(&config.image_path, &config.out_path, &config);
+    }
+
+    if is_glob_pattern(&config.image_path) {
+        let pattern = config.image_path.to_string_lossy();
+        return process_glob(&pattern, &config.out_path, &config);
+    }
+
+    render_file(config)
+}
+
+/// Renders every supported image in `in_dir` with the same settings, writing each to a file
+/// named after it in `out_dir`
+fn process_batch(in_dir: &Path, out_dir: &Path, config: &Config) -> Result<()> {
+    let mut paths = vec![];
+    for entry in
+        std::fs::read_dir(in_dir).with_context(|| format!("Reading {}", in_dir.display()))?
+    {
+        let path = entry.context("Reading directory entry")?.path();
+        if path.is_file() && is_supported_image(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    render_all(&paths, out_dir, config)
+}
+
+/// Renders every file matching the glob pattern like [`process_batch`] does
+fn process_glob(pattern: &str, out_dir: &Path, config: &Config) -> Result<()> {
+    let mut paths = vec![];
+    for entry in glob::glob(pattern).with_context(|| format!("Parsing pattern {:?}", pattern))? {
+        let path = entry.context("Matching pattern")?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    if paths.is_empty() {
+        bail!("No files match {:?}", pattern);
+    }
+
+    render_all(&paths, out_dir, config)
+}
+
+/// Renders each of the images into `out_dir`, carrying on past the ones that fail.
+/// Fails once every image has been tried if any of them failed, listing which and why
+fn render_all(paths: &[PathBuf], out_dir: &Path, config: &Config) -> Result<()> {
+    if !config.dry_run {
+        std::fs::create_dir_all(out_dir)
+            .with_context(|| format!("Creating {}", out_dir.display()))?;
+    }
+
+    let mut failures = vec![];
+    for path in paths {
+        if !config.quiet {
+            eprintln!("Rendering {}", path.display());
+        }
+        if let Err(err) = render_into(path, out_dir, config) {
+            eprintln!("{}: {:#}", path.display(), err);
+            failures.push((path, err));
+        }
+    }
+
+    if !failures.is_empty() {
+        eprintln!("Failed to render:");
+        for (path, err) in &failures {
+            eprintln!("  {}: {:#}", path.display(), err);
+        }
+        bail!("{} of {} images failed", failures.len(), paths.len());
+    }
+    Ok(())
+}
+
+/// Renders the image at `path` into a file named after it in `out_dir`,
+/// along with its PNG preview if one was asked for
+fn render_into(path: &Path, out_dir: &Path, config: &Config) -> Result<()> {
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_config = config.clone();
+    file_config.image_path = path.to_path_buf();
+    file_config.out_path = out_dir.join(format!("{}.{}", name, config.format.extension()));
+    file_config.png_path = config
+        .png_path
+        .as_ref()
+        .map(|_| out_dir.join(format!("{}.png", name)));
+    render_file(file_config)
+}
+
+/// Posterizes the colors, merges same-colored tiles into regions and outlines those,
+/// leaving alone whatever was set explicitly. Returns the ids of the flags it set
+fn apply_cel_preset(cli: &mut Cli) -> Vec<&'static str> {
+    let mut set = vec![];
+    if cli.posterize.is_none() {
+        cli.posterize = Some(2);
+        set.push("posterize");
+    }
+    if !cli.merge {
+        cli.merge = true;
+        set.push("merge");
+    }
+    if cli.region_borders.is_none() {
+        cli.region_borders = Some("#222".to_string());
+        set.push("region_borders");
+    }
+    // Thick enough to read as an ink line at any triangle size
+    if cli.stroke_width == DEFAULT_STROKE_WIDTH {
+        cli.stroke_width = cli.height / 30.;
+        set.push("stroke_width");
+    }
+    set
+}
+
+/// Renders the images matching the pattern as the frames of one animated SVG
+fn render_animation(pattern: &str, mut config: Config) -> Result<()> {
+    let mut paths = vec![];
+    for entry in glob::glob(pattern).with_context(|| format!("Parsing pattern {:?}", pattern))? {
+        let path = entry.context("Matching pattern")?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    if paths.is_empty() {
+        bail!("No frames match {:?}", pattern);
+    }
+
+    let frames = paths
+        .iter()
+        .map(|path| load_image_rgba(path).with_context(|| format!("Loading {}", path.display())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (image_width, image_data) = &frames[0];
+    let image_height = image_data.len().checked_div(image_width * 4).unwrap_or(0);
+    if *image_width == 0 || image_height == 0 {
+        bail!(
+            "Frames are {}x{}, but need at least one pixel in each direction",
+            image_width,
+            image_height
+        );
+    }
+
+    // The metadata names the pattern the frames came from
+    config.image_path = pattern.into();
+    config.render.metadata = Some(render_metadata(&config, *image_width, image_height));
+
+    let document = build_animated_document(
+        &frames,
+        config.n_vertical_tris,
+        config.triangle_height,
+        config.fps,
+        &config.render,
+    )?;
+
+    let mut output = CountingWriter::new(open_render_output(&config)?);
+    svg::write(&mut output, &document).context("Writing document")?;
+    output.flush().context("Writing output")?;
+
+    if config.dry_run {
+        println!(
+            "Would write an animation of {} frames, about {}",
+            frames.len(),
+            format_size(output.written)
+        );
+    } else if !config.quiet {
+        eprintln!(
+            "Wrote an animation of {} frames, about {}",
+            frames.len(),
+            format_size(output.written)
+        );
+    }
+
+    Ok(())
+}
+
+/// Renders the single image the settings point to
+fn render_file(mut config: Config) -> Result<()> {
+    if config.info {
+        return print_info(&config);
+    }
 
     // Load image
-    let (image_width, image_data) = load_png_from_path(image_path).context("Loading image")?;
-    let image_height = image_data.len() / (image_width * 3);
-    
-    if image_data.is_empty() {
-        bail!("Empty image");
-    }
-
-    // Ratio of half the base of a triangle to it's height
-    let sqrt_3 = (3.0_f32).sqrt();
-
-    // Number of triangles horizontally
-    let n_horiz_tris = (image_width * n_vertical_tris) / image_height;
-    let n_horiz_tris = (n_horiz_tris as f32 * sqrt_3) as usize;
-
-    // Half of the width of the base of a triangle. Useful for stepping along the grid
-    let half_triangle_width = triangle_height / sqrt_3;
-
-    // Generate triangles
-    let mut document = svg::Document::new().set(
-        "viewBox",
-        (
-            0,
-            0,
-            n_horiz_tris as f32 * half_triangle_width,
-            n_vertical_tris as f32 * triangle_height,
-        ),
-    );
+    let (image_width, image_data) = match (config.raw, config.image_path == Path::new("-")) {
+        (Some((width, height)), true) => load_raw_rgba(std::io::stdin().lock(), width, height)
+            .context("Loading raw image from stdin")?,
+        (Some((width, height)), false) => {
+            load_raw_input_rgba(open_input_file(&config.image_path)?, width, height)
+                .context("Loading raw image")?
+        }
+        (None, true) => {
+            load_png_rgba(std::io::stdin().lock()).context("Loading image from stdin")?
+        }
+        (None, false) => load_image_rgba(&config.image_path).context("Loading image")?,
+    };
+
+    let image_height = image_data.len().checked_div(image_width * 4).unwrap_or(0);
+    if image_width == 0 || image_height == 0 {
+        bail!(
+            "Image is {}x{}, but needs at least one pixel in each direction",
+            image_width,
+            image_height
+        );
+    }
+
+    if let Some(crop) = config.render.crop {
+        if crop.width == 0 || crop.height == 0 {
+            bail!("Crop region must not be empty");
+        }
+        if crop.x + crop.width > image_width || crop.y + crop.height > image_height {
+            bail!(
+                "Crop region {}x{} at ({}, {}) does not fit in the {}x{} image",
+                crop.width,
+                crop.height,
+                crop.x,
+                crop.y,
+                image_width,
+                image_height
+            );
+        }
+    }
+
+    if let Some(max_tiles) = config.max_triangles {
+        fit_tile_count(&mut config, image_width, image_height, max_tiles)?;
+    }
+
+    if let Some(min_height) = config.min_height {
+        fit_row_height(&mut config, image_width, image_height, min_height);
+    }
+
+    config.render.metadata = Some(render_metadata(&config, image_width, image_height));
+
+    if config.bench {
+        bench(&config, image_width, &image_data);
+        return Ok(());
+    }
 
-    let mut y = 0.0;
-    for row in 0..n_vertical_tris {
-        let mut x = 0.0;
-        for col in 0..=n_horiz_tris {
-            let img_y = ((row * image_height) / n_vertical_tris).min(image_height-1);
-            let img_x = ((col * image_width) / n_horiz_tris).min(image_width-1);
-            let img_idx = img_x + img_y * image_width;
-            let subpixel_idx = img_idx*3;
+    // Only draw a progress bar for people watching the terminal
+    if !config.quiet && std::io::stderr().is_terminal() {
+        let progress = ProgressBar::new(config.n_vertical_tris as u64).with_style(
+            ProgressStyle::with_template("{bar:40} {pos}/{len} rows")
+                .expect("Progress bar template is valid"),
+        );
+        config.render.progress = Some(progress);
+    }
 
-            let rgb = [
-                image_data[subpixel_idx+0],
-                image_data[subpixel_idx+1],
-                image_data[subpixel_idx+2],
-            ];
+    let mut output = CountingWriter::new(open_render_output(&config)?);
+    let stats = match config.format {
+        OutputFormat::Svg if config.stream => stream_document_with_stats(
+            &mut output,
+            image_width,
+            &image_data,
+            config.n_vertical_tris,
+            config.triangle_height,
+            &config.render,
+        )
+        .context("Streaming document")?,
+        OutputFormat::Svg => {
+            let (document, stats) = build_document_rgba_with_stats(
+                image_width,
+                &image_data,
+                config.n_vertical_tris,
+                config.triangle_height,
+                &config.render,
+            );
 
-            let points_up = (row & 1 == 0) != (col & 1 == 0);
+            if let Some(png_path) = &config.png_path {
+                let (view_width, view_height) =
+                    view_box_size(&document).context("Measuring document for PNG preview")?;
+                let png_height =
+                    (config.png_width as f32 * view_height / view_width).round() as u32;
+                let png = render_to_png(&document, config.png_width, png_height.max(1))?;
+                std::fs::write(png_path, png).context("Saving PNG preview")?;
+            }
 
-            let color = encode_color(rgb);
+            svg::write(&mut output, &document).context("Writing document")?;
+            stats
+        }
+        format => {
+            let cells = triangle_cells_rgba(
+                image_width,
+                &image_data,
+                config.n_vertical_tris,
+                config.triangle_height,
+                &config.render,
+            );
 
-            document.append(triangle_at(x, y, half_triangle_width, triangle_height, points_up, &color));
-            
-            x += half_triangle_width;
+            match format {
+                OutputFormat::Dxf => write_dxf(&mut output, &cells).context("Writing DXF")?,
+                OutputFormat::Eps => {
+                    let (image_width, image_height) =
+                        tiled_size(image_width, image_height, &config.render);
+                    let grid = TriangleGrid::new(
+                        image_width,
+                        image_height,
+                        config.n_vertical_tris,
+                        config.triangle_height,
+                        &config.render,
+                    );
+                    let view_size = (grid.view_width(), grid.view_height());
+                    write_scaled_eps(&mut output, &cells, view_size, config.render.physical_width)
+                        .context("Writing EPS")?
+                }
+                OutputFormat::Json => write_json(&mut output, &cells).context("Writing JSON")?,
+                OutputFormat::Csv => write_csv(&mut output, &cells).context("Writing CSV")?,
+                OutputFormat::Svg => unreachable!("SVG output is handled above"),
+            }
+            RenderStats::from_cells(&cells)
         }
-        y += triangle_height;
+    };
+    output.flush().context("Writing output")?;
+
+    if let Some(progress) = &config.render.progress {
+        progress.finish_and_clear();
     }
 
-    svg::save(svg_path, &document).context("Saving document")?;
+    if config.dry_run {
+        println!(
+            "Would write {} {} in {} colors, about {}",
+            stats.tiles,
+            tile_name(config.render.shape),
+            stats.colors,
+            format_size(output.written)
+        );
+    } else if !config.quiet {
+        eprintln!(
+            "Wrote {} {} in {} colors, about {}",
+            stats.tiles,
+            tile_name(config.render.shape),
+            stats.colors,
+            format_size(output.written)
+        );
+    }
 
     Ok(())
 }
 
-fn triangle_at(x: f32, y: f32, half_width: f32, height: f32, points_up: bool, color: &str) -> SvgPath {
-    let data = if points_up {
-        SvgData::new()
-            .move_to((x, y))
-            .line_by((-half_width, height))
-            .line_by((half_width * 2., 0.))
+/// Generates the mosaic without writing it, printing how long that took
+fn bench(config: &Config, image_width: usize, image_data: &[u8]) {
+    let start = Instant::now();
+    let stats = match config.format {
+        OutputFormat::Svg => {
+            build_document_rgba_with_stats(
+                image_width,
+                image_data,
+                config.n_vertical_tris,
+                config.triangle_height,
+                &config.render,
+            )
+            .1
+        }
+        _ => RenderStats::from_cells(&triangle_cells_rgba(
+            image_width,
+            image_data,
+            config.n_vertical_tris,
+            config.triangle_height,
+            &config.render,
+        )),
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let tiles = tile_name(config.render.shape);
+    eprintln!("Generated {} {} in {:.3} s", stats.tiles, tiles, elapsed);
+    eprintln!("{:.0} {} per second", stats.tiles as f64 / elapsed, tiles);
+}
+
+/// Formats a byte count with a binary unit, like `12.3 KiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit + 1 < UNITS.len() {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
     } else {
-        SvgData::new()
-            .move_to((x, y + height))
-            .line_by((-half_width, -height))
-            .line_by((half_width * 2., 0.))
+        format!("{:.1} {}", size, UNITS[unit])
     }
-    .close();
+}
+
+/// Passes writes through while counting the bytes written
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
 
-    SvgPath::new()
-        .set("fill", color)
-        .set("stroke", "none")
-        .set("stroke-width", 0.001)
-        .set("d", data)
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
 }
 
-fn encode_color([r, g, b]: [u8; 3]) -> String {
-    format!("#{:02X}{:02X}{:02X}", r, g, b)
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
 }
 
-fn load_png_from_path<P: AsRef<Path>>(path: P) -> Result<(usize, Vec<u8>)> {
-    let file = std::fs::File::open(path).context("Opening file")?;
-    let reader = std::io::BufReader::new(file);
-    load_png_rgb(reader)
+/// Lowers the number of rows until the grid has at most `max_tiles` tiles,
+/// saying so unless told to be quiet
+fn fit_tile_count(
+    config: &mut Config,
+    image_width: usize,
+    image_height: usize,
+    max_tiles: usize,
+) -> Result<()> {
+    let fits = rows_within_tile_count(
+        image_width,
+        image_height,
+        config.n_vertical_tris,
+        config.triangle_height,
+        &config.render,
+        max_tiles,
+    );
+    let Some(fits) = fits else {
+        bail!(
+            "Even a single row has more than {} tiles; lower --horizontal or raise --max-triangles",
+            max_tiles
+        );
+    };
+    if fits == config.n_vertical_tris {
+        return Ok(());
+    }
+
+    if !config.quiet {
+        let (n_cols, n_rows) = tile_grid_size(
+            image_width,
+            image_height,
+            fits,
+            config.triangle_height,
+            &config.render,
+        );
+        eprintln!(
+            "Reduced the grid from {} to {} rows ({} x {} tiles) to stay within {} tiles",
+            config.n_vertical_tris, fits, n_cols, n_rows, max_tiles
+        );
+    }
+    config.n_vertical_tris = fits;
+    Ok(())
 }
 
-/// Returns (width, rgb data) for the given PNG image reader
-fn load_png_rgb<R: Read>(r: R) -> Result<(usize, Vec<u8>)> {
-    let decoder = png::Decoder::new(r);
-    let mut reader = decoder.read_info().context("Creating reader")?;
+/// Lowers the number of rows until each is at least `min_height` pixels tall in the PNG
+/// preview, or in the source image without one, saying so unless told to be quiet
+fn fit_row_height(config: &mut Config, image_width: usize, image_height: usize, min_height: f32) {
+    let (width, height) = tiled_size(image_width, image_height, &config.render);
+
+    // Vertical triangle grids stack their rows from left to right
+    let vertical = config.render.shape == Shape::Triangle
+        && config.render.orientation == Orientation::Vertical;
+    let extent = match (config.png_path.is_some(), vertical) {
+        (true, true) => config.png_width as f32,
+        (true, false) => config.png_width as f32 * height as f32 / width as f32,
+        (false, true) => width as f32,
+        (false, false) => height as f32,
+    };
+
+    let max_rows = ((extent / min_height) as usize).max(1);
+    if config.n_vertical_tris <= max_rows {
+        return;
+    }
 
-    let mut buf = vec![0; reader.output_buffer_size()];
-    let info = reader.next_frame(&mut buf).context("Reading frame")?;
+    if !config.quiet {
+        eprintln!(
+            "Warning: {} rows would only be {:.2} pixels tall each; using {} rows of at least {} pixels",
+            config.n_vertical_tris,
+            extent / config.n_vertical_tris as f32,
+            max_rows,
+            min_height
+        );
+    }
+    config.n_vertical_tris = max_rows;
+}
 
-    if info.bit_depth != png::BitDepth::Eight {
-        bail!("Bit depth {:?} unsupported!", info.bit_depth);
+/// Plural name of the tiles of the given shape
+fn tile_name(shape: Shape) -> &'static str {
+    match shape {
+        Shape::Triangle => "triangles",
+        Shape::Hex => "hexagons",
+        Shape::Square => "squares",
+        Shape::Diamond => "diamonds",
     }
+}
+
+/// Describes the render for the document's metadata: where it came from and the grid used
+fn render_metadata(config: &Config, image_width: usize, image_height: usize) -> DocumentMetadata {
+    let (n_cols, n_rows) = tile_grid_size(
+        image_width,
+        image_height,
+        config.n_vertical_tris,
+        config.triangle_height,
+        &config.render,
+    );
+    let source = if config.image_path == Path::new("-") {
+        "stdin".to_string()
+    } else {
+        config.image_path.display().to_string()
+    };
+    let tiles = tile_name(config.render.shape);
 
-    buf.truncate(info.buffer_size());
+    DocumentMetadata {
+        title: config.title.clone(),
+        description: Some(format!(
+            "Mosaic of {}x{} {} from {}",
+            n_cols, n_rows, tiles, source
+        )),
+        properties: vec![
+            ("source".into(), source),
+            ("grid".into(), format!("{}x{}", n_cols, n_rows)),
+            ("tiles".into(), tiles.into()),
+            ("tile-height".into(), config.triangle_height.to_string()),
+        ],
+    }
+}
 
-    let buf: Vec<u8> = match info.color_type {
-        png::ColorType::Rgb => buf,
-        png::ColorType::Rgba => buf
-            .chunks_exact(4)
-            .map(|px| [px[0], px[1], px[2]])
-            .flatten()
-            .collect(),
-        png::ColorType::Grayscale => buf.iter().map(|&px| [px; 3]).flatten().collect(),
-        png::ColorType::GrayscaleAlpha => {
-            buf.chunks_exact(2).map(|px| [px[0]; 3]).flatten().collect()
+/// Prints details of the source image and the grid that would be laid over it
+fn print_info(config: &Config) -> Result<()> {
+    let info = if let Some((width, height)) = config.raw {
+        ImageInfo {
+            width,
+            height,
+            color_type: "Rgb".to_string(),
+            bit_depth: 8,
         }
-        other => bail!("Images with color type {:?} are unsupported", other),
+    } else if config.image_path == Path::new("-") {
+        png_info(std::io::stdin().lock()).context("Reading image from stdin")?
+    } else {
+        image_info(&config.image_path).context("Reading image")?
     };
 
-    Ok((info.width as usize, buf))
-}
\ No newline at end of file
+    let (n_horiz_tris, n_vertical_tris) = triangle_grid_size(
+        info.width,
+        info.height,
+        config.n_vertical_tris,
+        &config.render,
+    );
+
+    println!("Size: {}x{}", info.width, info.height);
+    println!("Color type: {}", info.color_type);
+    println!("Bit depth: {}", info.bit_depth);
+    println!("Grid: {} x {} triangles", n_horiz_tris, n_vertical_tris);
+
+    Ok(())
+}
+
+/// Writes the triangles as EPS, scaled from the mosaic's `view_size` to the physical width
+/// if there is one, or to an inch per unit otherwise
+fn write_scaled_eps<W: Write>(
+    w: W,
+    cells: &[(Triangle, [u8; 4])],
+    (view_width, view_height): (f32, f32),
+    physical_width: Option<PhysicalWidth>,
+) -> Result<()> {
+    let scale = physical_width.map_or(72., |width| width.points() / view_width);
+    let cells: Vec<_> = cells
+        .iter()
+        .map(|&(triangle, rgba)| {
+            let scaled = Triangle {
+                x: triangle.x * scale,
+                y: triangle.y * scale,
+                half_width: triangle.half_width * scale,
+                height: triangle.height * scale,
+                ..triangle
+            };
+            (scaled, rgba)
+        })
+        .collect();
+    write_eps(w, &cells, view_width * scale, view_height * scale)
+}
+
+/// Parses a size written as `WxH`, with both sides greater than zero
+fn parse_size(s: &str) -> Result<(usize, usize)> {
+    let (width, height) = s
+        .split_once('x')
+        .with_context(|| format!("Expected a size like 640x480, got {:?}", s))?;
+    let size = (width.parse()?, height.parse()?);
+    if size.0 == 0 || size.1 == 0 {
+        bail!("Both sides of the size must be greater than zero");
+    }
+    Ok(size)
+}
+
+/// Whether the input names no file but looks like a glob pattern, such as `frames/*.png`
+fn is_glob_pattern(path: &Path) -> bool {
+    !path.exists() && path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Lets every switch also take an explicit value, as in `--merge=false`, so the command line
+/// can turn off one that a --config file turned on
+fn switches_take_values(command: Command) -> Command {
+    command.mut_args(|arg| {
+        if let ArgAction::SetTrue = arg.get_action() {
+            arg.action(ArgAction::Set)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_value("false")
+                .default_missing_value("true")
+                .value_parser(BoolishValueParser::new())
+                .value_name("BOOL")
+                .hide_default_value(true)
+                .hide_possible_values(true)
+        } else {
+            arg
+        }
+    })
+}
+
+/// Reads a TOML, or JSON, file of settings
+fn read_settings(path: &Path) -> Result<Settings> {
+    let text = std::fs::read_to_string(path).context("Opening file")?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text).context("Parsing JSON")
+    } else {
+        toml::from_str(&text).context("Parsing TOML")
+    }
+}
+
+/// Checks the flags given on the command line, or set by the --config file or --preset as
+/// listed in `merged`, against each other. Clap already rejects conflicts within the command
+/// line, but not those involving the file or preset, nor missing [`REQUIREMENTS`]
+fn check_flags(command: &Command, matches: &ArgMatches, merged: &[&str]) -> Result<()> {
+    let given = |id: &str| {
+        merged.contains(&id) || matches.value_source(id) == Some(ValueSource::CommandLine)
+    };
+    let flag = |id: &str| {
+        let arg = command.get_arguments().find(|arg| arg.get_id() == id);
+        format!("--{}", arg.and_then(Arg::get_long).unwrap_or(id))
+    };
+
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        for other in command.get_arg_conflicts_with(arg) {
+            let other = other.get_id().as_str();
+            if given(id) && given(other) && (merged.contains(&id) || merged.contains(&other)) {
+                bail!("{} can't be used with {}", flag(id), flag(other));
+            }
+        }
+    }
+
+    for &(id, needs) in REQUIREMENTS {
+        if given(id) && !needs.iter().any(|need| given(need)) {
+            let needs: Vec<String> = needs.iter().map(|need| flag(need)).collect();
+            bail!("{} can only be used with {}", flag(id), needs.join(" or "));
+        }
+    }
+    Ok(())
+}
+
+/// Deserializes a setting written just like its flag's value, such as `shape = "hex"`
+fn parsed_setting<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(D::Error::custom)
+}
+
+/// Like [`parsed_setting`], for flags that may be left out entirely
+fn optional_parsed_setting<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    parsed_setting(deserializer).map(Some)
+}
+
+/// Deserializes a size setting written like its flag's value, such as `resize = "640x480"`
+fn size_setting<'de, D>(deserializer: D) -> Result<Option<Option<(usize, usize)>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_size(&value)
+        .map(|size| Some(Some(size)))
+        .map_err(D::Error::custom)
+}
+
+/// Opens the file the mosaic is written to, or on a dry run somewhere that only swallows it
+fn open_render_output(config: &Config) -> Result<Box<dyn Write>> {
+    if config.dry_run {
+        Ok(Box::new(std::io::sink()))
+    } else {
+        open_output(&config.out_path)
+    }
+}
+
+/// Opens the file at `path` for writing, or stdout if the path is -
+fn open_output(path: &Path) -> Result<Box<dyn Write>> {
+    if path == Path::new("-") {
+        Ok(Box::new(std::io::stdout().lock()))
+    } else {
+        let file = File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+        Ok(Box::new(BufWriter::new(file)))
+    }
+}
@@ -0,0 +1,96 @@
+use crate::shape::{round_data, tile_path, TileStyle, Triangle};
+use crate::{encode_color, with_alpha};
+use std::collections::HashMap;
+use svg::node::element::{path::Data as SvgData, Path as SvgPath};
+
+/// Groups edge-adjacent triangles of the same color and opacity into regions,
+/// and emits each region as a single path with one subpath per triangle
+pub fn merge_by_color(triangles: &[(Triangle, [u8; 4])], style: &TileStyle) -> Vec<SvgPath> {
+    merge_regions(triangles, style)
+        .into_iter()
+        .map(|(data, [r, g, b, a])| with_alpha(tile_path(data, &encode_color([r, g, b]), style), a))
+        .collect()
+}
+
+/// Returns the outline and color of each same-colored region of edge-adjacent triangles
+pub(crate) fn merge_regions(
+    triangles: &[(Triangle, [u8; 4])],
+    style: &TileStyle,
+) -> Vec<(SvgData, [u8; 4])> {
+    let index_of: HashMap<(usize, usize), usize> = triangles
+        .iter()
+        .enumerate()
+        .map(|(idx, (tri, _))| ((tri.row, tri.col), idx))
+        .collect();
+
+    // Union-find over triangle indices
+    let mut parent: Vec<usize> = (0..triangles.len()).collect();
+    fn root(parent: &mut [usize], mut idx: usize) -> usize {
+        while parent[idx] != idx {
+            parent[idx] = parent[parent[idx]];
+            idx = parent[idx];
+        }
+        idx
+    }
+
+    for (idx, (tri, rgba)) in triangles.iter().enumerate() {
+        // Neighbors share a slanted side to the right, or the base below an upward triangle
+        let right = (tri.row, tri.col + 1);
+        let below = (tri.row + 1, tri.col);
+        let neighbors = std::iter::once(right).chain(tri.points_up.then_some(below));
+
+        for neighbor in neighbors {
+            if let Some(&other) = index_of.get(&neighbor) {
+                if triangles[other].1 == *rgba {
+                    let (a, b) = (root(&mut parent, idx), root(&mut parent, other));
+                    parent[a] = b;
+                }
+            }
+        }
+    }
+
+    // Collect regions in order of their first triangle, keeping output deterministic
+    let mut region_of_root: HashMap<usize, usize> = HashMap::new();
+    let mut regions: Vec<(SvgData, [u8; 4])> = vec![];
+    for (idx, (tri, rgba)) in triangles.iter().enumerate() {
+        let region = *region_of_root
+            .entry(root(&mut parent, idx))
+            .or_insert_with(|| {
+                regions.push((SvgData::new(), *rgba));
+                regions.len() - 1
+            });
+
+        let (data, _) = &mut regions[region];
+        *data = tri.append_outline(std::mem::take(data), style);
+    }
+
+    regions
+}
+
+/// Returns the sides shared by edge-adjacent triangles of different colors, tracing the
+/// borders between same-colored regions. The sides are those of the undistorted grid,
+/// without any gap or jitter
+pub fn region_borders(triangles: &[(Triangle, [u8; 4])], style: &TileStyle) -> SvgData {
+    let color_at: HashMap<(usize, usize), [u8; 4]> = triangles
+        .iter()
+        .map(|(tri, rgba)| ((tri.row, tri.col), *rgba))
+        .collect();
+
+    let mut data = SvgData::new();
+    for (tri, rgba) in triangles {
+        let [apex, base_left, base_right] = tri.corners();
+        // The same neighbors as when merging: the slanted side to the right, and the base
+        // below an upward triangle
+        let right = ((tri.row, tri.col + 1), (apex, base_right));
+        let below = ((tri.row + 1, tri.col), (base_left, base_right));
+        let neighbors = std::iter::once(right).chain(tri.points_up.then_some(below));
+
+        for (neighbor, (from, to)) in neighbors {
+            if color_at.get(&neighbor).is_some_and(|other| other != rgba) {
+                data = data.move_to(from).line_to(to);
+            }
+        }
+    }
+
+    round_data(data, style.precision)
+}
@@ -0,0 +1,52 @@
+use svg::node::element::Element;
+use svg::node::{Comment, Text};
+use svg::Node;
+
+/// Provenance recorded at the top of the document
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    /// Shown as the document's `<title>`
+    pub title: Option<String>,
+    /// Shown as the document's `<desc>`
+    pub description: Option<String>,
+    /// Name and value pairs listed one per line in `<metadata>`, such as the source file
+    pub properties: Vec<(String, String)>,
+}
+
+/// Appends a generator comment, followed by whichever of the title, description and
+/// properties are present
+pub(crate) fn append_metadata<N: Node>(document: &mut N, metadata: &DocumentMetadata) {
+    document.append(Comment::new(format!(
+        "Generated by {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )));
+
+    // Written as raw text, since elements put their children on lines of their own and
+    // viewers would show those line breaks as part of the title
+    if let Some(title) = &metadata.title {
+        document.append(Text::new(format!("<title>{}</title>", escape(title))));
+    }
+
+    if let Some(description) = &metadata.description {
+        document.append(Text::new(format!("<desc>{}</desc>", escape(description))));
+    }
+
+    if !metadata.properties.is_empty() {
+        let lines: Vec<String> = metadata
+            .properties
+            .iter()
+            .map(|(name, value)| format!("{}: {}", escape(name), escape(value)))
+            .collect();
+        let mut element = Element::new("metadata");
+        element.append(Text::new(lines.join("\n")));
+        document.append(element);
+    }
+}
+
+/// Escapes the characters that would otherwise be read as markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
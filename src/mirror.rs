@@ -0,0 +1,41 @@
+/// Places an image next to its reflections across its right and bottom edges,
+/// returning the width and data of the twice as wide and twice as tall result.
+/// The result matches itself along opposite edges, so anything tiled over it repeats seamlessly
+pub(crate) fn mirror_pixels(
+    image_width: usize,
+    image_data: &[u8],
+    channels: usize,
+) -> (usize, Vec<u8>) {
+    let row_len = image_width * channels;
+    let rows: Vec<&[u8]> = image_data.chunks_exact(row_len).collect();
+
+    let mut mirrored = Vec::with_capacity(image_data.len() * 4);
+    for row in rows.iter().chain(rows.iter().rev()) {
+        mirrored.extend_from_slice(row);
+        for px in row.chunks_exact(channels).rev() {
+            mirrored.extend_from_slice(px);
+        }
+    }
+
+    (image_width * 2, mirrored)
+}
+
+/// Swaps the rows and columns of an image, reflecting it across its main diagonal,
+/// and returns the width and data of the result
+pub(crate) fn transpose_pixels(
+    image_width: usize,
+    image_data: &[u8],
+    channels: usize,
+) -> (usize, Vec<u8>) {
+    let image_height = image_data.len() / (image_width * channels).max(1);
+
+    let mut transposed = Vec::with_capacity(image_data.len());
+    for x in 0..image_width {
+        for y in 0..image_height {
+            let idx = (x + y * image_width) * channels;
+            transposed.extend_from_slice(&image_data[idx..idx + channels]);
+        }
+    }
+
+    (image_height, transposed)
+}
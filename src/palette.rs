@@ -0,0 +1,113 @@
+use crate::color::rgb_to_lab;
+use crate::shape::position_hash;
+use anyhow::{bail, Context, Error, Result};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Color space in which the distance to each palette entry is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PaletteSpace {
+    /// Euclidean distance between the sRGB values
+    #[default]
+    Rgb,
+    /// ΔE, the Euclidean distance in CIELAB, which tracks perceived difference
+    Lab,
+}
+
+impl FromStr for PaletteSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rgb" => Ok(PaletteSpace::Rgb),
+            "lab" => Ok(PaletteSpace::Lab),
+            other => bail!("Unknown palette space {:?} (expected rgb or lab)", other),
+        }
+    }
+}
+
+/// Picks a palette entry at random for the tile at (row, col), the same every time for
+/// the same seed. An empty palette gives black
+pub(crate) fn random_palette_color(
+    palette: &[[u8; 3]],
+    seed: u64,
+    (row, col): (usize, usize),
+) -> [u8; 3] {
+    if palette.is_empty() {
+        return [0; 3];
+    }
+    let hash = position_hash(seed, (row as i64, col as i64));
+    palette[(hash % palette.len() as u64) as usize]
+}
+
+/// Returns the palette entry closest to `color`, measuring distance in `space`.
+/// An empty palette leaves the color as it is
+pub fn nearest_palette(color: [u8; 3], palette: &[[u8; 3]], space: PaletteSpace) -> [u8; 3] {
+    let target = coordinates(color, space);
+    let distance =
+        |entry: &[u8; 3]| -> f32 { squared_distance(coordinates(*entry, space), target) };
+
+    palette
+        .iter()
+        .copied()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(color)
+}
+
+/// Returns the distance between two colors, measured in `space`
+pub fn color_distance(a: [u8; 3], b: [u8; 3], space: PaletteSpace) -> f32 {
+    squared_distance(coordinates(a, space), coordinates(b, space)).sqrt()
+}
+
+fn coordinates(rgb: [u8; 3], space: PaletteSpace) -> [f32; 3] {
+    match space {
+        PaletteSpace::Rgb => rgb.map(|channel| channel as f32),
+        PaletteSpace::Lab => rgb_to_lab(rgb),
+    }
+}
+
+fn squared_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+/// Parses a `#RRGGBB` or `#RGB` hex color, with or without the leading `#`
+pub fn parse_hex_color(text: &str) -> Result<[u8; 3]> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("Invalid hex color {:?}", text);
+    }
+
+    let channel = |i: usize, len: usize| u8::from_str_radix(&digits[i * len..(i + 1) * len], 16);
+    match digits.len() {
+        6 => Ok([channel(0, 2)?, channel(1, 2)?, channel(2, 2)?]),
+        3 => Ok([
+            channel(0, 1)? * 17,
+            channel(1, 1)? * 17,
+            channel(2, 1)? * 17,
+        ]),
+        _ => bail!("Invalid hex color {:?}", text),
+    }
+}
+
+/// Loads a palette file listing one hex color per line. Blank lines are skipped
+pub fn load_palette(path: impl AsRef<Path>) -> Result<Vec<[u8; 3]>> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading palette {}", path.display()))?;
+
+    let palette = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            parse_hex_color(line.trim())
+                .with_context(|| format!("On line {} of {}", idx + 1, path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if palette.is_empty() {
+        bail!("Palette {} has no colors", path.display());
+    }
+
+    Ok(palette)
+}
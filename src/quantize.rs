@@ -0,0 +1,169 @@
+use crate::color::posterize;
+use crate::palette::{nearest_palette, PaletteSpace};
+
+/// Most refinement passes k-means makes before settling for its current clusters
+const MAX_ITERATIONS: usize = 32;
+
+/// Groups `colors` into at most `k` clusters with k-means, returning each color
+/// snapped to the centroid of its cluster, in the same order as the input.
+/// The initial centroids are spread evenly over the distinct input colors, so the
+/// result is deterministic
+pub fn quantize_kmeans(colors: &[[u8; 3]], k: usize) -> Vec<[u8; 3]> {
+    let (palette, assignments) = kmeans(colors, k);
+    assignments.iter().map(|&idx| palette[idx]).collect()
+}
+
+/// Returns the palette of at most `k` colors that [`quantize_kmeans`] snaps `colors` to
+pub(crate) fn kmeans_palette(colors: &[[u8; 3]], k: usize) -> Vec<[u8; 3]> {
+    kmeans(colors, k).0
+}
+
+/// Clusters `colors` into at most `k` groups, returning the centroid of each
+/// cluster along with the cluster each color was assigned to
+fn kmeans(colors: &[[u8; 3]], k: usize) -> (Vec<[u8; 3]>, Vec<usize>) {
+    let mut distinct = colors.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+
+    // Nothing to reduce
+    if k == 0 || distinct.len() <= k {
+        let assignments = colors
+            .iter()
+            .map(|color| distinct.binary_search(color).unwrap_or(0))
+            .collect();
+        return (distinct, assignments);
+    }
+
+    let mut centroids: Vec<[f32; 3]> = (0..k)
+        .map(|i| distinct[i * distinct.len() / k].map(|channel| channel as f32))
+        .collect();
+
+    let mut assignments = vec![0; colors.len()];
+    for iteration in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (assignment, &color) in assignments.iter_mut().zip(colors) {
+            let nearest = nearest_centroid(&centroids, color);
+            if nearest != *assignment || iteration == 0 {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![([0u64; 3], 0u64); k];
+        for (&assignment, color) in assignments.iter().zip(colors) {
+            let (sum, count) = &mut sums[assignment];
+            for (total, &channel) in sum.iter_mut().zip(color) {
+                *total += channel as u64;
+            }
+            *count += 1;
+        }
+
+        // Clusters that lost all their colors keep their previous centroid
+        for (centroid, (sum, count)) in centroids.iter_mut().zip(sums) {
+            if count > 0 {
+                *centroid = sum.map(|total| total as f32 / count as f32);
+            }
+        }
+    }
+
+    let palette: Vec<[u8; 3]> = centroids
+        .iter()
+        .map(|centroid| centroid.map(|channel| channel.round() as u8))
+        .collect();
+
+    (palette, assignments)
+}
+
+/// Returns the index of the centroid closest to `color`
+fn nearest_centroid(centroids: &[[f32; 3]], color: [u8; 3]) -> usize {
+    let distance = |centroid: &[f32; 3]| -> f32 {
+        centroid
+            .iter()
+            .zip(color)
+            .map(|(c, channel)| (c - channel as f32).powi(2))
+            .sum()
+    };
+
+    (0..centroids.len())
+        .min_by(|&a, &b| distance(&centroids[a]).total_cmp(&distance(&centroids[b])))
+        .unwrap_or(0)
+}
+
+/// Snaps colors to the reduced set of colors the render options ask for
+pub(crate) struct Quantizer {
+    /// Bits kept per channel
+    pub posterize: Option<u8>,
+    /// Colors every tile must be snapped to
+    pub palette: Option<Vec<[u8; 3]>>,
+    /// Where distances to the palette are measured
+    pub palette_space: PaletteSpace,
+}
+
+impl Quantizer {
+    /// Whether quantizing leaves every color as it is
+    pub fn is_identity(&self) -> bool {
+        self.posterize.is_none() && self.palette.is_none()
+    }
+
+    /// Returns the reduced color closest to `rgb`
+    pub fn quantize(&self, mut rgb: [u8; 3]) -> [u8; 3] {
+        if let Some(bits) = self.posterize {
+            rgb = posterize(rgb, bits);
+        }
+        if let Some(palette) = &self.palette {
+            rgb = nearest_palette(rgb, palette, self.palette_space);
+        }
+        rgb
+    }
+}
+
+/// Quantizes `colors` in place, laid out on a grid where each color sits at the
+/// (row, col) given for it. The quantization error of each color is diffused
+/// onto the neighbors that come after it with Floyd–Steinberg weights
+pub(crate) fn dither(colors: &mut [[u8; 3]], positions: &[(usize, usize)], quantizer: &Quantizer) {
+    let n_rows = positions.iter().map(|&(row, _)| row + 1).max().unwrap_or(0);
+    let n_cols = positions.iter().map(|&(_, col)| col + 1).max().unwrap_or(0);
+
+    // Index of the color at each grid position, if there is one
+    let mut grid = vec![None; n_rows * n_cols];
+    for (idx, &(row, col)) in positions.iter().enumerate() {
+        grid[row * n_cols + col] = Some(idx);
+    }
+
+    let mut error = vec![[0f32; 3]; colors.len()];
+    for row in 0..n_rows {
+        for col in 0..n_cols {
+            let Some(idx) = grid[row * n_cols + col] else {
+                continue;
+            };
+
+            let mut wanted = [0f32; 3];
+            for ((wanted, &channel), err) in wanted.iter_mut().zip(&colors[idx]).zip(error[idx]) {
+                *wanted = (channel as f32 + err).clamp(0., 255.);
+            }
+
+            let quantized = quantizer.quantize(wanted.map(|channel| channel.round() as u8));
+            colors[idx] = quantized;
+
+            let neighbors = [(0, 1, 7.), (1, -1, 3.), (1, 0, 5.), (1, 1, 1.)];
+            for (d_row, d_col, weight) in neighbors {
+                let (row, col) = (row + d_row, col as isize + d_col);
+                if row >= n_rows || col < 0 || col as usize >= n_cols {
+                    continue;
+                }
+                let Some(neighbor) = grid[row * n_cols + col as usize] else {
+                    continue;
+                };
+                for ((err, wanted), quantized) in
+                    error[neighbor].iter_mut().zip(wanted).zip(quantized)
+                {
+                    *err += (wanted - quantized as f32) * weight / 16.;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use anyhow::{bail, Context, Result};
+use resvg::{tiny_skia, usvg};
+
+/// Rasterizes the document to a `width` by `height` PNG, stretching its view box to fill the image
+pub fn render_to_png(document: &svg::Document, width: u32, height: u32) -> Result<Vec<u8>> {
+    let tree = usvg::Tree::from_str(&document.to_string(), &usvg::Options::default())
+        .context("Parsing document for rasterization")?;
+
+    let Some(mut pixmap) = tiny_skia::Pixmap::new(width, height) else {
+        bail!("Invalid PNG size {}x{}", width, height);
+    };
+
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    pixmap.encode_png().context("Encoding PNG")
+}
+
+/// Returns the width and height of the document's view box, if it has one
+pub fn view_box_size(document: &svg::Document) -> Option<(f32, f32)> {
+    let view_box = document.get_inner().get_attributes().get("viewBox")?;
+    let mut values = view_box.split_whitespace().skip(2).map(str::parse);
+    match (values.next(), values.next()) {
+        (Some(Ok(width)), Some(Ok(height))) => Some((width, height)),
+        _ => None,
+    }
+}
@@ -0,0 +1,50 @@
+/// Rescales an RGB image to `new_width` x `new_height` with a box filter,
+/// returning the new width and data.
+/// Each output pixel averages the source pixels under it, or repeats the nearest one when enlarging
+pub fn resize_rgb(
+    image_width: usize,
+    image_data: &[u8],
+    new_width: usize,
+    new_height: usize,
+) -> (usize, Vec<u8>) {
+    resize_pixels(image_width, image_data, 3, new_width, new_height)
+}
+
+/// Like [`resize_rgb`], for images with any number of interleaved channels per pixel
+pub(crate) fn resize_pixels(
+    image_width: usize,
+    image_data: &[u8],
+    channels: usize,
+    new_width: usize,
+    new_height: usize,
+) -> (usize, Vec<u8>) {
+    let image_height = image_data.len() / (image_width * channels);
+
+    // The half-open span of source pixels under output pixel `i` along one axis
+    let span = |i: usize, new_len: usize, len: usize| {
+        let start = (i * len / new_len).min(len - 1);
+        let end = ((i + 1) * len / new_len).clamp(start + 1, len);
+        start..end
+    };
+
+    let mut resized = Vec::with_capacity(new_width * new_height * channels);
+    for y in 0..new_height {
+        let rows = span(y, new_height, image_height);
+        for x in 0..new_width {
+            let cols = span(x, new_width, image_width);
+            let count = (rows.len() * cols.len()) as u64;
+            for channel in 0..channels {
+                let sum: u64 = rows
+                    .clone()
+                    .flat_map(|row| cols.clone().map(move |col| (row, col)))
+                    .map(|(row, col)| {
+                        image_data[(row * image_width + col) * channels + channel] as u64
+                    })
+                    .sum();
+                resized.push(((sum + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    (new_width, resized)
+}
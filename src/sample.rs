@@ -0,0 +1,501 @@
+use crate::color::{linear_to_srgb, luminance, oklab_to_rgb, rgb_to_oklab, srgb_to_linear};
+use crate::edges::strongest_edge;
+use crate::palette::color_distance;
+use crate::RenderOptions;
+use anyhow::{bail, Error, Result};
+use std::str::FromStr;
+
+/// How the color of each triangle is picked from the source image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SampleMode {
+    /// Use the single pixel under the triangle's grid position
+    #[default]
+    Nearest,
+    /// Interpolate between the four pixels around the triangle's grid position
+    Bilinear,
+    /// Average every pixel covered by the triangle's bounding box
+    Average,
+    /// Take the per-channel median of every pixel covered by the triangle's bounding box,
+    /// which keeps edges sharper than averaging on noisy images
+    Median,
+}
+
+impl FromStr for SampleMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nearest" => Ok(SampleMode::Nearest),
+            "bilinear" => Ok(SampleMode::Bilinear),
+            "average" => Ok(SampleMode::Average),
+            "median" => Ok(SampleMode::Median),
+            other => bail!(
+                "Unknown sample mode {:?} (expected nearest, bilinear, average or median)",
+                other
+            ),
+        }
+    }
+}
+
+/// Where within each triangle its grid position, and so its sample, lies
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SamplePoint {
+    /// The top edge of the row, level with the apex of downward triangles
+    #[default]
+    Corner,
+    /// The centroid of the triangle, which sits lower in upward triangles than in downward ones
+    Centroid,
+}
+
+impl FromStr for SamplePoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "corner" => Ok(SamplePoint::Corner),
+            "centroid" => Ok(SamplePoint::Centroid),
+            other => bail!(
+                "Unknown sample point {:?} (expected corner or centroid)",
+                other
+            ),
+        }
+    }
+}
+
+/// Color space in which source pixels are averaged together
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum AverageSpace {
+    /// Average the encoded sRGB values directly
+    #[default]
+    Srgb,
+    /// Average in linear light, which avoids darkening blends
+    Linear,
+    /// Average values raised to this power, then take the matching root of the result,
+    /// for sources encoded with a plain gamma curve
+    Gamma(f32),
+    /// Average in Oklab, which keeps blends of distant hues vivid rather than gray
+    Oklab,
+}
+
+impl FromStr for AverageSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "srgb" => Ok(AverageSpace::Srgb),
+            "linear" => Ok(AverageSpace::Linear),
+            "oklab" => Ok(AverageSpace::Oklab),
+            other => bail!(
+                "Unknown average space {:?} (expected srgb, linear or oklab)",
+                other
+            ),
+        }
+    }
+}
+
+/// Returns the RGB pixel at (x, y)
+pub fn pixel_at(image_width: usize, image_data: &[u8], x: usize, y: usize) -> [u8; 3] {
+    let subpixel_idx = (x + y * image_width) * 3;
+    [
+        image_data[subpixel_idx],
+        image_data[subpixel_idx + 1],
+        image_data[subpixel_idx + 2],
+    ]
+}
+
+/// Interpolates the RGB color at (fx, fy) from the four pixels around it.
+/// Pixel (x, y) covers `[x, x + 1) x [y, y + 1)`, so its own color is found at its center,
+/// and positions past the outermost centers take the color of the edge
+pub fn sample_bilinear(image_width: usize, image_data: &[u8], fx: f32, fy: f32) -> [u8; 3] {
+    let image_height = image_data.len() / (image_width * 3);
+    let mut sum = [0f32; 3];
+    for (idx, weight) in bilinear_weights(image_width, image_height, fx, fy) {
+        for (total, &channel) in sum.iter_mut().zip(&image_data[idx * 3..idx * 3 + 3]) {
+            *total += channel as f32 * weight;
+        }
+    }
+    sum.map(|total| total.round() as u8)
+}
+
+/// Returns the indices of the four pixels around (fx, fy) along with their weights
+fn bilinear_weights(
+    image_width: usize,
+    image_height: usize,
+    fx: f32,
+    fy: f32,
+) -> [(usize, f32); 4] {
+    // Splits a coordinate into the two pixels on either side and the weight of the second
+    let split = |f: f32, size: usize| {
+        let f = (f - 0.5).clamp(0., (size - 1) as f32);
+        let lower = f.floor() as usize;
+        (lower, (lower + 1).min(size - 1), f - lower as f32)
+    };
+    let (x0, x1, tx) = split(fx, image_width);
+    let (y0, y1, ty) = split(fy, image_height);
+
+    [
+        (x0 + y0 * image_width, (1. - tx) * (1. - ty)),
+        (x1 + y0 * image_width, tx * (1. - ty)),
+        (x0 + y1 * image_width, (1. - tx) * ty),
+        (x1 + y1 * image_width, tx * ty),
+    ]
+}
+
+/// Iterates over the pixel indices in the half-open box `[x0, x1) x [y0, y1)`.
+/// The box is clamped to the image and always covers at least one pixel.
+fn indices_in_box(
+    image_width: usize,
+    image_height: usize,
+    (x0, y0): (usize, usize),
+    (x1, y1): (usize, usize),
+) -> impl Iterator<Item = usize> {
+    let x0 = x0.min(image_width - 1);
+    let y0 = y0.min(image_height - 1);
+    let x1 = x1.clamp(x0 + 1, image_width);
+    let y1 = y1.clamp(y0 + 1, image_height);
+
+    (y0..y1).flat_map(move |y| (x0..x1).map(move |x| x + y * image_width))
+}
+
+/// Iterates over the pixels in the half-open box `[x0, x1) x [y0, y1)`.
+/// The box is clamped to the image and always covers at least one pixel.
+fn pixels_in_box(
+    image_width: usize,
+    image_data: &[u8],
+    min: (usize, usize),
+    max: (usize, usize),
+) -> impl Iterator<Item = [u8; 3]> + '_ {
+    let image_height = image_data.len() / (image_width * 3);
+    indices_in_box(image_width, image_height, min, max).map(move |idx| {
+        pixel_at(
+            image_width,
+            image_data,
+            idx % image_width,
+            idx / image_width,
+        )
+    })
+}
+
+/// Averages the source pixels covered by the box `[img_x0, img_x1) x [img_y0, img_y1)`
+pub fn average_triangle_color(
+    image_width: usize,
+    image_data: &[u8],
+    img_x0: usize,
+    img_y0: usize,
+    img_x1: usize,
+    img_y1: usize,
+    space: AverageSpace,
+) -> [u8; 3] {
+    let pixels = pixels_in_box(image_width, image_data, (img_x0, img_y0), (img_x1, img_y1));
+    average_pixels(pixels, space)
+}
+
+/// Averages the N x N grid of source pixels spread evenly over the box
+/// `[img_x0, img_x1) x [img_y0, img_y1)`. Boxes fewer than N pixels across use each of
+/// their pixels once along that direction instead
+pub fn supersample_color(
+    image_width: usize,
+    image_data: &[u8],
+    (img_x0, img_y0): (usize, usize),
+    (img_x1, img_y1): (usize, usize),
+    n: usize,
+    space: AverageSpace,
+) -> [u8; 3] {
+    let image_height = image_data.len() / (image_width * 3);
+    let indices = supersample_indices(
+        image_width,
+        image_height,
+        (img_x0, img_y0),
+        (img_x1, img_y1),
+        n,
+    );
+    let pixels = indices.map(|idx| {
+        pixel_at(
+            image_width,
+            image_data,
+            idx % image_width,
+            idx / image_width,
+        )
+    });
+    average_pixels(pixels, space)
+}
+
+/// Iterates over the indices of an N x N grid of pixels spread evenly over the box
+/// `[x0, x1) x [y0, y1)`, clamped the same way as [`indices_in_box`]
+fn supersample_indices(
+    image_width: usize,
+    image_height: usize,
+    (x0, y0): (usize, usize),
+    (x1, y1): (usize, usize),
+    n: usize,
+) -> impl Iterator<Item = usize> {
+    let x0 = x0.min(image_width - 1);
+    let y0 = y0.min(image_height - 1);
+    let x1 = x1.clamp(x0 + 1, image_width);
+    let y1 = y1.clamp(y0 + 1, image_height);
+
+    // Places `count` samples at the centers of equal slices of `[start, end)`
+    let steps = move |start: usize, end: usize| {
+        let count = n.clamp(1, end - start);
+        (0..count).map(move |i| start + ((2 * i + 1) * (end - start)) / (2 * count))
+    };
+
+    steps(y0, y1).flat_map(move |y| steps(x0, x1).map(move |x| x + y * image_width))
+}
+
+/// Averages a non-empty run of pixels in the given color space
+fn average_pixels(pixels: impl Iterator<Item = [u8; 3]>, space: AverageSpace) -> [u8; 3] {
+    match space {
+        AverageSpace::Srgb => {
+            let mut sum = [0u64; 3];
+            let mut count = 0;
+            for px in pixels {
+                for (total, channel) in sum.iter_mut().zip(px) {
+                    *total += channel as u64;
+                }
+                count += 1;
+            }
+            sum.map(|total| ((total + count / 2) / count) as u8)
+        }
+        AverageSpace::Linear => average_decoded(pixels, srgb_to_linear, linear_to_srgb),
+        AverageSpace::Gamma(gamma) => average_decoded(
+            pixels,
+            |channel| (channel as f32 / 255.).powf(gamma),
+            |value| (value.powf(1. / gamma) * 255.).round().clamp(0., 255.) as u8,
+        ),
+        AverageSpace::Oklab => {
+            let mut sum = [0f32; 3];
+            let mut count = 0;
+            for px in pixels {
+                for (total, component) in sum.iter_mut().zip(rgb_to_oklab(px)) {
+                    *total += component;
+                }
+                count += 1;
+            }
+            oklab_to_rgb(sum.map(|total| total / count as f32))
+        }
+    }
+}
+
+/// Averages a non-empty run of pixels after decoding each channel, encoding the mean again
+fn average_decoded(
+    pixels: impl Iterator<Item = [u8; 3]>,
+    decode: impl Fn(u8) -> f32,
+    encode: impl Fn(f32) -> u8,
+) -> [u8; 3] {
+    let mut sum = [0f32; 3];
+    let mut count = 0;
+    for px in pixels {
+        for (total, channel) in sum.iter_mut().zip(px) {
+            *total += decode(channel);
+        }
+        count += 1;
+    }
+    sum.map(|total| encode(total / count as f32))
+}
+
+/// Takes the per-channel median of the source pixels covered by the box
+/// `[img_x0, img_x1) x [img_y0, img_y1)`
+pub fn median_triangle_color(
+    image_width: usize,
+    image_data: &[u8],
+    img_x0: usize,
+    img_y0: usize,
+    img_x1: usize,
+    img_y1: usize,
+) -> [u8; 3] {
+    let mut channels = [vec![], vec![], vec![]];
+    for px in pixels_in_box(image_width, image_data, (img_x0, img_y0), (img_x1, img_y1)) {
+        for (samples, channel) in channels.iter_mut().zip(px) {
+            samples.push(channel);
+        }
+    }
+    channels.map(|mut samples| median(&mut samples))
+}
+
+/// Returns the lower median of a non-empty slice, reordering it in the process
+fn median(samples: &mut [u8]) -> u8 {
+    let middle = (samples.len() - 1) / 2;
+    *samples.select_nth_unstable(middle).1
+}
+
+/// Returns how much the source pixels in the box `[min, max)` vary: the variance of each
+/// channel, in 8-bit units, averaged over the three channels
+pub(crate) fn color_variance(source: &Source, min: (usize, usize), max: (usize, usize)) -> f32 {
+    if source.width == 0 || source.height == 0 {
+        return 0.;
+    }
+    let mut sum = [0f64; 3];
+    let mut sum_sq = [0f64; 3];
+    let mut count = 0;
+    for px in pixels_in_box(source.width, source.rgb, min, max) {
+        for ((sum, sum_sq), channel) in sum.iter_mut().zip(&mut sum_sq).zip(px) {
+            *sum += channel as f64;
+            *sum_sq += (channel as f64).powi(2);
+        }
+        count += 1;
+    }
+
+    let count = count as f64;
+    let variance: f64 = sum
+        .iter()
+        .zip(sum_sq)
+        .map(|(sum, sum_sq)| sum_sq / count - (sum / count).powi(2))
+        .sum();
+    (variance / 3.) as f32
+}
+
+/// The image being tiled
+pub(crate) struct Source<'a> {
+    pub width: usize,
+    pub height: usize,
+    /// Interleaved RGB data
+    pub rgb: &'a [u8],
+    /// One alpha sample per pixel, if the image carries transparency
+    pub alpha: Option<&'a [u8]>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(width: usize, rgb: &'a [u8], alpha: Option<&'a [u8]>) -> Self {
+        Self {
+            width,
+            height: rgb.len() / (width * 3).max(1),
+            rgb,
+            alpha,
+        }
+    }
+}
+
+/// Picks a color for a cell whose grid position lands on `at`, in fractional pixels,
+/// and which covers the box `[min, max)` of source pixels.
+/// The alpha channel is only kept if the options ask for it, and is opaque otherwise.
+/// Returns `None` for cells that should be left out entirely.
+pub(crate) fn sample_cell(
+    source: &Source,
+    options: &RenderOptions,
+    at: (f64, f64),
+    min: (usize, usize),
+    max: (usize, usize),
+) -> Option<[u8; 4]> {
+    // An empty image has nothing to sample, so its tiles are all left out
+    if source.width == 0 || source.height == 0 {
+        return None;
+    }
+    let mut pixel = (
+        (at.0.max(0.) as usize).min(source.width - 1),
+        (at.1.max(0.) as usize).min(source.height - 1),
+    );
+    let (mut at, mut min, mut max) = (at, min, max);
+
+    // Flips reflect where the cell samples, rather than the image itself
+    if options.flip_h {
+        pixel.0 = source.width - 1 - pixel.0;
+        at.0 = source.width as f64 - at.0;
+        (min.0, max.0) = (
+            source.width.saturating_sub(max.0),
+            source.width.saturating_sub(min.0),
+        );
+    }
+    if options.flip_v {
+        pixel.1 = source.height - 1 - pixel.1;
+        at.1 = source.height as f64 - at.1;
+        (min.1, max.1) = (
+            source.height.saturating_sub(max.1),
+            source.height.saturating_sub(min.1),
+        );
+    }
+
+    let [r, g, b] = match (options.supersample, options.sample) {
+        (Some(n), _) => {
+            supersample_color(source.width, source.rgb, min, max, n, options.average_space)
+        }
+        (None, SampleMode::Nearest) => pixel_at(source.width, source.rgb, pixel.0, pixel.1),
+        (None, SampleMode::Bilinear) => {
+            sample_bilinear(source.width, source.rgb, at.0 as f32, at.1 as f32)
+        }
+        (None, SampleMode::Average) => average_triangle_color(
+            source.width,
+            source.rgb,
+            min.0,
+            min.1,
+            max.0,
+            max.1,
+            options.average_space,
+        ),
+        (None, SampleMode::Median) => {
+            median_triangle_color(source.width, source.rgb, min.0, min.1, max.0, max.1)
+        }
+    };
+
+    let alpha = match source.alpha {
+        Some(alpha) => match (options.supersample, options.sample) {
+            (Some(n), _) => {
+                let indices = supersample_indices(source.width, source.height, min, max, n);
+                let (sum, count) = indices.fold((0u64, 0u64), |(sum, count), idx| {
+                    (sum + alpha[idx] as u64, count + 1)
+                });
+                ((sum + count / 2) / count) as u8
+            }
+            (None, SampleMode::Nearest) => alpha[pixel.0 + pixel.1 * source.width],
+            (None, SampleMode::Bilinear) => {
+                let weights =
+                    bilinear_weights(source.width, source.height, at.0 as f32, at.1 as f32);
+                let sum: f32 = weights
+                    .iter()
+                    .map(|&(idx, weight)| alpha[idx] as f32 * weight)
+                    .sum();
+                sum.round() as u8
+            }
+            (None, SampleMode::Average) => {
+                let indices = indices_in_box(source.width, source.height, min, max);
+                let (sum, count) = indices.fold((0u64, 0u64), |(sum, count), idx| {
+                    (sum + alpha[idx] as u64, count + 1)
+                });
+                ((sum + count / 2) / count) as u8
+            }
+            (None, SampleMode::Median) => {
+                let indices = indices_in_box(source.width, source.height, min, max);
+                let mut samples: Vec<u8> = indices.map(|idx| alpha[idx]).collect();
+                median(&mut samples)
+            }
+        },
+        None => 255,
+    };
+
+    if options
+        .skip_transparent
+        .is_some_and(|threshold| alpha < threshold)
+    {
+        return None;
+    }
+
+    if options.chroma_key.is_some_and(|key| {
+        color_distance([r, g, b], key, options.palette_space) <= options.chroma_tolerance
+    }) {
+        return None;
+    }
+
+    if options
+        .edges
+        .is_some_and(|threshold| strongest_edge(source, min, max) < threshold)
+    {
+        return None;
+    }
+
+    let [r, g, b] = options.color.apply([r, g, b]);
+
+    // Only the dark, opaque parts of the image are covered in random colors
+    if options.randomize_colors && (alpha < 128 || luminance([r, g, b]) >= 128.) {
+        return None;
+    }
+
+    let alpha = if options.keep_alpha { alpha } else { 255 };
+
+    if let Some([ink_r, ink_g, ink_b]) = options.ink {
+        let darkness = 255 - luminance([r, g, b]).round() as u8;
+        let alpha = (alpha as u16 * darkness as u16 + 127) / 255;
+        return Some([ink_r, ink_g, ink_b, alpha as u8]);
+    }
+
+    Some([r, g, b, alpha])
+}
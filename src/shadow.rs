@@ -0,0 +1,36 @@
+use crate::shape::round_to;
+use crate::RenderOptions;
+use svg::node::element::{Definitions, Element, Filter, Group};
+use svg::Node;
+
+/// Id of the filter casting the mosaic's drop shadow
+const FILTER_ID: &str = "shadow";
+
+/// A shadow cast by the whole mosaic onto whatever is behind it, for a layered paper look
+#[derive(Clone, Debug, PartialEq)]
+pub struct DropShadow {
+    /// How far right the shadow falls, in SVG units
+    pub dx: f32,
+    /// How far down the shadow falls, in SVG units
+    pub dy: f32,
+    /// Standard deviation of the blur softening the shadow, in SVG units
+    pub blur: f32,
+    /// Color of the shadow, as any SVG color
+    pub color: String,
+}
+
+/// If the options ask for a shadow, appends the filter casting it to the document's defs and
+/// returns an empty group using it, for the tiles to be drawn into
+pub(crate) fn shadow_group(document: &mut svg::Document, options: &RenderOptions) -> Option<Group> {
+    let shadow = options.shadow.as_ref()?;
+    let precision = options.style.precision;
+
+    let mut drop_shadow = Element::new("feDropShadow");
+    drop_shadow.assign("dx", round_to(shadow.dx, precision));
+    drop_shadow.assign("dy", round_to(shadow.dy, precision));
+    drop_shadow.assign("stdDeviation", round_to(shadow.blur, precision));
+    drop_shadow.assign("flood-color", shadow.color.as_str());
+
+    document.append(Definitions::new().add(Filter::new().set("id", FILTER_ID).add(drop_shadow)));
+    Some(Group::new().set("filter", format!("url(#{})", FILTER_ID)))
+}
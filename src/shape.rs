@@ -0,0 +1,572 @@
+use anyhow::{bail, Error, Result};
+use std::str::FromStr;
+use svg::node::element::path::{Command, Parameters};
+use svg::node::element::{path::Data as SvgData, Path as SvgPath, Polygon};
+
+/// The kind of tile the image is divided into
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Shape {
+    /// Alternating up and down pointing triangles
+    #[default]
+    Triangle,
+    /// Pointy-top hexagons with every other row offset by half a cell
+    Hex,
+    /// A plain grid of squares, like enlarged pixels
+    Square,
+    /// Upright rhombi, each an up and a down pointing triangle sharing their base,
+    /// with every other row offset by half a cell like an isometric grid
+    Diamond,
+}
+
+impl FromStr for Shape {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "triangle" => Ok(Shape::Triangle),
+            "hex" => Ok(Shape::Hex),
+            "square" => Ok(Shape::Square),
+            "diamond" => Ok(Shape::Diamond),
+            other => bail!(
+                "Unknown shape {:?} (expected triangle, hex, square or diamond)",
+                other
+            ),
+        }
+    }
+}
+
+/// Which way the rows of the triangle grid run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Orientation {
+    /// Rows run left to right, with triangles pointing up and down
+    #[default]
+    Horizontal,
+    /// The grid is turned a quarter, so rows run top to bottom
+    /// and triangles point left and right
+    Vertical,
+}
+
+impl FromStr for Orientation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "horizontal" => Ok(Orientation::Horizontal),
+            "vertical" => Ok(Orientation::Vertical),
+            other => bail!(
+                "Unknown orientation {:?} (expected horizontal or vertical)",
+                other
+            ),
+        }
+    }
+}
+
+/// The SVG element each triangle is drawn with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TileElement {
+    /// A `<path>` moving to one corner and drawing lines to the other two
+    #[default]
+    Path,
+    /// A `<polygon>` listing the three corners, which some tools handle more robustly
+    Polygon,
+}
+
+impl FromStr for TileElement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "path" => Ok(TileElement::Path),
+            "polygon" => Ok(TileElement::Polygon),
+            other => bail!("Unknown element {:?} (expected path or polygon)", other),
+        }
+    }
+}
+
+/// Appearance shared by every tile
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileStyle {
+    /// Outline paint, as any SVG color
+    pub stroke: String,
+    pub stroke_width: f32,
+    /// Leave every tile unfilled, so only the outlines are drawn.
+    /// Set a stroke for there to be anything to see
+    pub outline_only: bool,
+    /// Width of the empty space left between neighboring tiles
+    pub gap: f32,
+    /// Number of decimals coordinates are rounded to, or `None` for full precision
+    pub precision: Option<u8>,
+    /// Largest distance, along each axis, that triangle corners are randomly moved by.
+    /// Corners shared between neighbors move together, so no gaps open up.
+    /// Ignored by `defs`, whose triangles all share the same two outlines
+    pub jitter: f32,
+    /// Seed for the jitter, so the same seed always produces the same mosaic
+    pub seed: u64,
+    /// Radius of the arcs replacing the corners of each triangle, or 0 for sharp corners.
+    /// Corners too tight for the radius are rounded as far as their edges allow
+    pub corner_radius: f32,
+}
+
+impl Default for TileStyle {
+    fn default() -> Self {
+        Self {
+            stroke: "none".to_string(),
+            stroke_width: 0.001,
+            outline_only: false,
+            gap: 0.,
+            precision: Some(4),
+            jitter: 0.,
+            seed: 0,
+            corner_radius: 0.,
+        }
+    }
+}
+
+/// A single cell of the triangle grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Triangle {
+    /// Row of the cell in the grid
+    pub row: usize,
+    /// Column of the cell in the grid
+    pub col: usize,
+    /// Horizontal center
+    pub x: f32,
+    /// Top edge
+    pub y: f32,
+    /// Half of the width of the base
+    pub half_width: f32,
+    pub height: f32,
+    pub points_up: bool,
+}
+
+impl Triangle {
+    /// Creates a filled path for this triangle
+    pub fn to_path(&self, color: &str, style: &TileStyle) -> SvgPath {
+        triangle_at(
+            self.x,
+            self.y,
+            self.half_width,
+            self.height,
+            self.points_up,
+            color,
+            style,
+        )
+    }
+
+    /// Creates a filled polygon for this triangle. Corners are never rounded
+    pub fn to_polygon(&self, color: &str, style: &TileStyle) -> Polygon {
+        let points: Vec<String> = outline_corners(
+            self.x,
+            self.y,
+            self.half_width,
+            self.height,
+            self.points_up,
+            style,
+        )
+        .iter()
+        .map(|&(x, y)| {
+            format!(
+                "{},{}",
+                round_to(x, style.precision),
+                round_to(y, style.precision)
+            )
+        })
+        .collect();
+
+        Polygon::new()
+            .set("points", points.join(" "))
+            .set("fill", fill_color(color, style))
+            .set("stroke", style.stroke.as_str())
+            .set("stroke-width", style.stroke_width)
+    }
+
+    /// Returns the three corners of this triangle, ignoring any gap
+    pub fn corners(&self) -> [(f32, f32); 3] {
+        let (apex_y, base_y) = if self.points_up {
+            (self.y, self.y + self.height)
+        } else {
+            (self.y + self.height, self.y)
+        };
+        [
+            (self.x, apex_y),
+            (self.x - self.half_width, base_y),
+            (self.x + self.half_width, base_y),
+        ]
+    }
+
+    /// Returns the centroid of this triangle, the mean of its three corners
+    pub fn centroid(&self) -> (f32, f32) {
+        let (x, y) = self
+            .corners()
+            .iter()
+            .fold((0., 0.), |(x, y), &(cx, cy)| (x + cx, y + cy));
+        (x / 3., y / 3.)
+    }
+
+    /// Appends the outline of this triangle to `data` as a closed subpath
+    pub fn append_outline(&self, data: SvgData, style: &TileStyle) -> SvgData {
+        triangle_outline(
+            data,
+            self.x,
+            self.y,
+            self.half_width,
+            self.height,
+            self.points_up,
+            style,
+        )
+    }
+}
+
+/// Creates a triangle horizontally centered on `x`, spanning from `y` to `y + height`
+pub fn triangle_at(
+    x: f32,
+    y: f32,
+    half_width: f32,
+    height: f32,
+    points_up: bool,
+    color: &str,
+    style: &TileStyle,
+) -> SvgPath {
+    let data = triangle_outline(SvgData::new(), x, y, half_width, height, points_up, style);
+    tile_path(data, color, style)
+}
+
+fn triangle_outline(
+    data: SvgData,
+    x: f32,
+    y: f32,
+    half_width: f32,
+    height: f32,
+    points_up: bool,
+    style: &TileStyle,
+) -> SvgData {
+    if style.jitter > 0. || style.corner_radius > 0. {
+        let corners = outline_corners(x, y, half_width, height, points_up, style);
+        if style.corner_radius > 0. {
+            return rounded_outline(data, &corners, style.corner_radius);
+        }
+        return data
+            .move_to(corners[0])
+            .line_to(corners[1])
+            .line_to(corners[2])
+            .close();
+    }
+
+    let (y, half_width, height) = if style.gap > 0. {
+        inset_triangle(y, half_width, height, points_up, style.gap)
+    } else {
+        (y, half_width, height)
+    };
+    if points_up {
+        data.move_to((x, y))
+            .line_by((-half_width, height))
+            .line_by((half_width * 2., 0.))
+    } else {
+        data.move_to((x, y + height))
+            .line_by((-half_width, -height))
+            .line_by((half_width * 2., 0.))
+    }
+    .close()
+}
+
+/// Returns the apex and then the two base corners of a triangle as it is drawn,
+/// after insetting it by the gap and jittering it
+fn outline_corners(
+    x: f32,
+    y: f32,
+    half_width: f32,
+    height: f32,
+    points_up: bool,
+    style: &TileStyle,
+) -> [(f32, f32); 3] {
+    let (inset_y, inset_half_width, inset_height) = if style.gap > 0. {
+        inset_triangle(y, half_width, height, points_up, style.gap)
+    } else {
+        (y, half_width, height)
+    };
+
+    // Corners are keyed by where they sit on the grid before any inset,
+    // so every triangle meeting at a corner moves it the same way
+    let lattice = |x: f32, y: f32| lattice_point(x, y, half_width, height);
+    let (apex_y, base_y) = if points_up {
+        (y, y + height)
+    } else {
+        (y + height, y)
+    };
+    let (inset_apex_y, inset_base_y) = if points_up {
+        (inset_y, inset_y + inset_height)
+    } else {
+        (inset_y + inset_height, inset_y)
+    };
+    [
+        (lattice(x, apex_y), (x, inset_apex_y)),
+        (
+            lattice(x - half_width, base_y),
+            (x - inset_half_width, inset_base_y),
+        ),
+        (
+            lattice(x + half_width, base_y),
+            (x + inset_half_width, inset_base_y),
+        ),
+    ]
+    .map(|(key, (cx, cy))| {
+        if style.jitter > 0. {
+            let (dx, dy) = jitter_offset(key, style);
+            (cx + dx, cy + dy)
+        } else {
+            (cx, cy)
+        }
+    })
+}
+
+/// Appends the closed polygon through `corners` as a subpath,
+/// replacing each corner with an arc of the given radius tangent to both of its edges
+fn rounded_outline(mut data: SvgData, corners: &[(f32, f32)], radius: f32) -> SvgData {
+    let n = corners.len();
+    for i in 0..n {
+        let (px, py) = corners[(i + n - 1) % n];
+        let (cx, cy) = corners[i];
+        let (nx, ny) = corners[(i + 1) % n];
+
+        let (in_x, in_y, in_len) = (px - cx, py - cy, (px - cx).hypot(py - cy));
+        let (out_x, out_y, out_len) = (nx - cx, ny - cy, (nx - cx).hypot(ny - cy));
+        if in_len <= f32::EPSILON || out_len <= f32::EPSILON {
+            data = if i == 0 {
+                data.move_to((cx, cy))
+            } else {
+                data.line_to((cx, cy))
+            };
+            continue;
+        }
+
+        // The arc touches each edge `radius / tan(angle / 2)` away from the corner,
+        // but never past the middle of an edge, where the next corner's arc may start
+        let cos = ((in_x * out_x + in_y * out_y) / (in_len * out_len)).clamp(-1., 1.);
+        let half_tan = (cos.acos() / 2.).tan();
+        let cut = (radius / half_tan).min(in_len / 2.).min(out_len / 2.);
+        let radius = cut * half_tan;
+
+        let start = (cx + in_x / in_len * cut, cy + in_y / in_len * cut);
+        let end = (cx + out_x / out_len * cut, cy + out_y / out_len * cut);
+
+        // Turning clockwise on screen, where y points down, means sweeping clockwise too
+        let turn = (cx - px) * (ny - cy) - (cy - py) * (nx - cx);
+        let sweep = if turn > 0. { 1 } else { 0 };
+
+        data = if i == 0 {
+            data.move_to(start)
+        } else {
+            data.line_to(start)
+        };
+        data = data.elliptical_arc_to((radius, radius, 0, 0, sweep, end.0, end.1));
+    }
+    data.close()
+}
+
+/// Returns the offset of the grid corner at the given lattice point, each axis within
+/// `±style.jitter`
+fn jitter_offset((i, j, level): (i64, i64, i32), style: &TileStyle) -> (f32, f32) {
+    let hash = position_hash(position_hash(style.seed, (level as i64, 0)), (i, j));
+
+    // The top and bottom 32 bits each become a value in [-1, 1)
+    let unit = |bits: u64| (bits as u32 as f64 / u32::MAX as f64 * 2. - 1.) as f32;
+    (unit(hash) * style.jitter, unit(hash >> 32) * style.jitter)
+}
+
+/// Returns where the point (x, y) sits on the lattice of corners of triangles of the given
+/// size, as (i, j, level), the same for every size of triangle with a corner there.
+///
+/// Triangles split by `adaptive` or a density map are the regular grid's halved some
+/// number of times, which changes only the exponent of their size. So the exponent
+/// numbers the level of the lattice, and the point is moved to the coarsest level it lies on
+fn lattice_point(x: f32, y: f32, half_width: f32, height: f32) -> (i64, i64, i32) {
+    let (mut i, mut j) = ((x / half_width).round() as i64, (y / height).round() as i64);
+    if (i, j) == (0, 0) {
+        return (0, 0, 0);
+    }
+    let mut level = ((half_width.to_bits() >> 23) & 0xFF) as i32;
+    while i % 2 == 0 && j % 2 == 0 {
+        (i, j, level) = (i / 2, j / 2, level + 1);
+    }
+    (i, j, level)
+}
+
+/// Returns 64 random bits for the grid position (i, j), the same every time for the same seed
+pub(crate) fn position_hash(seed: u64, (i, j): (i64, i64)) -> u64 {
+    // SplitMix64, seeded per position
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    };
+    mix(seed ^ mix((i as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (j as u64).rotate_left(32)))
+}
+
+/// Scales a triangle toward its centroid so that each edge moves inward by `gap / 2`,
+/// returning the new (y, half_width, height)
+fn inset_triangle(
+    y: f32,
+    half_width: f32,
+    height: f32,
+    points_up: bool,
+    gap: f32,
+) -> (f32, f32, f32) {
+    // The inradius is the distance from the incenter to every edge; for the isosceles
+    // triangles on the grid the incenter lies on the same vertical as the centroid
+    let side = half_width.hypot(height);
+    let inradius = (half_width * height) / (half_width + side);
+    let scale = ((inradius - gap / 2.) / inradius).max(0.);
+
+    // The centroid sits a third of the way up from the base
+    let centroid_y = if points_up {
+        y + height * 2. / 3.
+    } else {
+        y + height / 3.
+    };
+
+    let (half_width, height) = (half_width * scale, height * scale);
+    let y = if points_up {
+        centroid_y - height * 2. / 3.
+    } else {
+        centroid_y - height / 3.
+    };
+
+    (y, half_width, height)
+}
+
+/// Creates a pointy-top hexagon centered on (cx, cy) with the given circumradius
+pub fn hexagon_at(cx: f32, cy: f32, radius: f32, color: &str, style: &TileStyle) -> SvgPath {
+    // Moving each edge inward by half the gap shrinks the circumradius by gap / sqrt(3)
+    let radius = (radius - style.gap / (3.0_f32).sqrt()).max(0.);
+    let half_width = radius * (3.0_f32).sqrt() / 2.;
+    let data = SvgData::new()
+        .move_to((cx, cy - radius))
+        .line_to((cx + half_width, cy - radius / 2.))
+        .line_to((cx + half_width, cy + radius / 2.))
+        .line_to((cx, cy + radius))
+        .line_to((cx - half_width, cy + radius / 2.))
+        .line_to((cx - half_width, cy - radius / 2.))
+        .close();
+
+    tile_path(data, color, style)
+}
+
+/// Creates a square with its top-left corner at (x, y)
+pub fn square_at(x: f32, y: f32, size: f32, color: &str, style: &TileStyle) -> SvgPath {
+    let inset = (style.gap / 2.).min(size / 2.);
+    let (x, y, size) = (x + inset, y + inset, size - inset * 2.);
+    let data = SvgData::new()
+        .move_to((x, y))
+        .horizontal_line_by(size)
+        .vertical_line_by(size)
+        .horizontal_line_by(-size)
+        .close();
+
+    tile_path(data, color, style)
+}
+
+/// Creates an upright rhombus filling the box `width` wide and `height` tall
+/// with its top-left corner at (x, y)
+pub fn diamond_at(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: &str,
+    style: &TileStyle,
+) -> SvgPath {
+    let (half_width, half_height) = (width / 2., height / 2.);
+    let (cx, cy) = (x + half_width, y + half_height);
+
+    // Scale toward the center until each edge has moved inward by half the gap
+    let apothem = half_width * half_height / half_width.hypot(half_height);
+    let scale = if apothem > 0. {
+        ((apothem - style.gap / 2.) / apothem).max(0.)
+    } else {
+        0.
+    };
+    let (half_width, half_height) = (half_width * scale, half_height * scale);
+
+    let data = SvgData::new()
+        .move_to((cx, cy - half_height))
+        .line_to((cx + half_width, cy))
+        .line_to((cx, cy + half_height))
+        .line_to((cx - half_width, cy))
+        .close();
+
+    tile_path(data, color, style)
+}
+
+/// Wraps the outline of one or more tiles in a filled path
+pub(crate) fn tile_path(data: SvgData, color: &str, style: &TileStyle) -> SvgPath {
+    SvgPath::new()
+        .set("fill", fill_color(color, style))
+        .set("stroke", style.stroke.as_str())
+        .set("stroke-width", style.stroke_width)
+        .set("d", round_data(data, style.precision))
+}
+
+/// Returns the fill a tile of the given color gets, which is `none` for outlines only
+pub(crate) fn fill_color<'a>(color: &'a str, style: &TileStyle) -> &'a str {
+    if style.outline_only {
+        "none"
+    } else {
+        color
+    }
+}
+
+/// Rounds `value` to `precision` decimals, or leaves it as is for `None`
+pub(crate) fn round_to(value: f32, precision: Option<u8>) -> f32 {
+    match precision {
+        Some(precision) => {
+            let scale = 10f64.powi(precision as i32);
+            ((value as f64 * scale).round() / scale) as f32
+        }
+        None => value,
+    }
+}
+
+/// Rounds every number in the path data to `precision` decimals
+pub(crate) fn round_data(data: SvgData, precision: Option<u8>) -> SvgData {
+    if precision.is_none() {
+        return data;
+    }
+
+    let round = |parameters: &Parameters| -> Parameters {
+        parameters
+            .iter()
+            .map(|&value| round_to(value, precision))
+            .collect::<Vec<_>>()
+            .into()
+    };
+
+    data.iter()
+        .map(|command| match command {
+            Command::Move(position, parameters) => Command::Move(*position, round(parameters)),
+            Command::Line(position, parameters) => Command::Line(*position, round(parameters)),
+            Command::HorizontalLine(position, parameters) => {
+                Command::HorizontalLine(*position, round(parameters))
+            }
+            Command::VerticalLine(position, parameters) => {
+                Command::VerticalLine(*position, round(parameters))
+            }
+            Command::QuadraticCurve(position, parameters) => {
+                Command::QuadraticCurve(*position, round(parameters))
+            }
+            Command::SmoothQuadraticCurve(position, parameters) => {
+                Command::SmoothQuadraticCurve(*position, round(parameters))
+            }
+            Command::CubicCurve(position, parameters) => {
+                Command::CubicCurve(*position, round(parameters))
+            }
+            Command::SmoothCubicCurve(position, parameters) => {
+                Command::SmoothCubicCurve(*position, round(parameters))
+            }
+            Command::EllipticalArc(position, parameters) => {
+                Command::EllipticalArc(*position, round(parameters))
+            }
+            Command::Close => Command::Close,
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
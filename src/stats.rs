@@ -0,0 +1,21 @@
+use std::collections::HashSet;
+
+/// Counts describing a finished render
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    /// Number of tiles drawn
+    pub tiles: usize,
+    /// Number of distinct fill colors, counting opacity
+    pub colors: usize,
+}
+
+impl RenderStats {
+    /// Counts the tiles and distinct colors of a list of colored cells
+    pub fn from_cells<T>(cells: &[(T, [u8; 4])]) -> Self {
+        let colors: HashSet<[u8; 4]> = cells.iter().map(|&(_, rgba)| rgba).collect();
+        Self {
+            tiles: cells.len(),
+            colors: colors.len(),
+        }
+    }
+}
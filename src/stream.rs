@@ -0,0 +1,119 @@
+use crate::palette::random_palette_color;
+use crate::quantize::Quantizer;
+use crate::sample::Source;
+use crate::shape::{Orientation, Shape, TileElement};
+use crate::tile::{new_document, TriangleRows};
+use crate::{encode_color, with_alpha, RenderOptions, RenderStats};
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Writes the triangle grid as an SVG a batch of rows at a time, so only the tiles of one
+/// batch are ever held in memory. The file matches the one the whole document would give
+pub(crate) fn stream_triangles<W: Write>(
+    mut w: W,
+    source: &Source,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> Result<RenderStats> {
+    check_streamable(options)?;
+
+    let rows = TriangleRows::new(source, n_vertical_tris, triangle_height, options);
+    let document = new_document(rows.view_width(), rows.view_height(), options);
+    let (head, foot) = split_document(&document);
+    w.write_all(head.as_bytes()).context("Writing SVG header")?;
+
+    // Fixed palettes and posterizing treat every tile on its own, so they can be streamed
+    let quantizer = Quantizer {
+        posterize: options.posterize,
+        palette: options.palette.clone(),
+        palette_space: options.palette_space,
+    };
+
+    let mut tiles = 0;
+    let mut colors = HashSet::new();
+    // Rows are still sampled in parallel, as many at a time as there are threads
+    let batch_size = rayon::current_num_threads().max(1);
+    for first_row in (0..rows.n_rows).step_by(batch_size) {
+        let last_row = (first_row + batch_size).min(rows.n_rows);
+        let batch: Vec<Vec<([u8; 4], String)>> = (first_row..last_row)
+            .into_par_iter()
+            .map(|row| {
+                rows.sample_row(row)
+                    .into_iter()
+                    .map(|(triangle, [r, g, b, a])| {
+                        let [r, g, b] = match (options.randomize_colors, &options.palette) {
+                            (true, Some(palette)) => random_palette_color(
+                                palette,
+                                options.style.seed,
+                                (triangle.row, triangle.col),
+                            ),
+                            _ => quantizer.quantize([r, g, b]),
+                        };
+                        let color = encode_color([r, g, b]);
+                        let tile = match options.element {
+                            TileElement::Path => {
+                                with_alpha(triangle.to_path(&color, &options.style), a).to_string()
+                            }
+                            TileElement::Polygon => {
+                                with_alpha(triangle.to_polygon(&color, &options.style), a)
+                                    .to_string()
+                            }
+                        };
+                        ([r, g, b, a], tile)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (rgba, tile) in batch.into_iter().flatten() {
+            write!(w, "\n{}", tile).context("Writing tile")?;
+            colors.insert(rgba);
+            tiles += 1;
+        }
+    }
+
+    w.write_all(foot.as_bytes()).context("Writing SVG footer")?;
+    Ok(RenderStats {
+        tiles,
+        colors: colors.len(),
+    })
+}
+
+/// Fails for options that need every tile at once, or a tree of groups around them
+fn check_streamable(options: &RenderOptions) -> Result<()> {
+    if options.shape != Shape::Triangle || options.orientation != Orientation::Horizontal {
+        bail!("Only horizontal triangle grids can be streamed");
+    }
+    if options.merge || options.classes || options.defs || options.layers_by_color {
+        bail!("Merged, class-based, defs and layered output can't be streamed");
+    }
+    if options.gradient {
+        bail!("Gradient fills can't be streamed");
+    }
+    if options.shadow.is_some() {
+        bail!("Drop shadows can't be streamed");
+    }
+    if options.region_borders.is_some() {
+        bail!("Region borders can't be streamed");
+    }
+    if options.colors.is_some() || options.dither {
+        bail!("Reducing to k-means colors and dithering can't be streamed");
+    }
+    Ok(())
+}
+
+/// Splits the serialized document around where its tiles go
+fn split_document(document: &svg::Document) -> (String, String) {
+    let document = document.to_string();
+    match document.strip_suffix("\n</svg>") {
+        Some(head) => (head.to_string(), "\n</svg>".to_string()),
+        // Documents without children are closed on the spot
+        None => {
+            let head = document.strip_suffix("/>").unwrap_or(&document);
+            (format!("{}>", head), "\n</svg>".to_string())
+        }
+    }
+}
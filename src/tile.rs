@@ -0,0 +1,743 @@
+use crate::adaptive::Subdivider;
+use crate::classes::{emit_outlines_with_classes, emit_with_classes};
+use crate::defs::emit_with_defs;
+use crate::gradient::{corner_colors, emit_with_gradients};
+use crate::grid::TriangleGrid;
+use crate::layers::emit_layers_by_color;
+use crate::merge::{merge_by_color, merge_regions, region_borders};
+use crate::metadata::append_metadata;
+use crate::mirror::transpose_pixels;
+use crate::palette::random_palette_color;
+use crate::quantize::{dither, kmeans_palette, Quantizer};
+use crate::sample::{sample_cell, Source};
+use crate::shadow::shadow_group;
+use crate::shape::{
+    diamond_at, hexagon_at, round_to, square_at, Orientation, TileElement, Triangle,
+};
+use crate::{encode_color, with_alpha, RenderOptions, RenderStats};
+use rayon::prelude::*;
+use svg::node::element::{Group, Path as SvgPath, Rectangle};
+use svg::Node;
+
+/// The alternating triangle grid, with a color sampled for every cell
+pub(crate) struct TriangleLayout {
+    pub view_width: f32,
+    pub view_height: f32,
+    pub cells: Vec<(Triangle, [u8; 4])>,
+    /// The colors under the corners of each cell, when the options ask for gradients
+    pub corner_colors: Vec<[[u8; 3]; 3]>,
+}
+
+/// Lays out the alternating triangle grid and samples each cell's color
+pub(crate) fn layout_triangles(
+    source: &Source,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> TriangleLayout {
+    let rows = TriangleRows::new(source, n_vertical_tris, triangle_height, options);
+
+    // Rows only read the source image, so they are sampled in parallel
+    let rows_cells: Vec<Vec<(Triangle, [u8; 4])>> = (0..rows.n_rows)
+        .into_par_iter()
+        .map(|row| rows.sample_row(row))
+        .collect();
+    let mut cells: Vec<_> = rows_cells.into_iter().flatten().collect();
+
+    // Triangles of different sizes can't share the regular grid's rows and columns
+    if let Some(subdivider) = &rows.subdivider {
+        for (triangle, _) in &mut cells {
+            (triangle.row, triangle.col) = subdivider.fine_position(triangle);
+        }
+    }
+
+    recolor_cells(&mut cells, options, |triangle| (triangle.row, triangle.col));
+
+    let corner_colors = if options.gradient {
+        corner_colors(source, options, rows.image_span(), &cells)
+    } else {
+        Vec::new()
+    };
+
+    TriangleLayout {
+        view_width: rows.view_width(),
+        view_height: rows.view_height(),
+        cells,
+        corner_colors,
+    }
+}
+
+/// The alternating triangle grid over a source image, sampled one row at a time
+pub(crate) struct TriangleRows<'a> {
+    source: &'a Source<'a>,
+    options: &'a RenderOptions,
+    subdivider: Option<Subdivider<'a>>,
+    grid: TriangleGrid,
+    pub n_rows: usize,
+}
+
+impl<'a> TriangleRows<'a> {
+    /// Prepares the grid of `n_vertical_tris` rows of triangles `triangle_height` tall
+    pub fn new(
+        source: &'a Source<'a>,
+        n_vertical_tris: usize,
+        triangle_height: f32,
+        options: &'a RenderOptions,
+    ) -> Self {
+        let grid = TriangleGrid::new(
+            source.width,
+            source.height,
+            n_vertical_tris,
+            triangle_height,
+            options,
+        );
+
+        let subdivider = options.splitting().map(|adaptive| {
+            Subdivider::new(
+                source,
+                options,
+                adaptive,
+                (grid.image_width_units(), grid.view_height()),
+                (grid.half_width(), triangle_height),
+            )
+        });
+
+        Self {
+            source,
+            options,
+            subdivider,
+            n_rows: grid.n_rows(),
+            grid,
+        }
+    }
+
+    /// Width of the document the grid fills
+    pub fn view_width(&self) -> f32 {
+        self.grid.view_width()
+    }
+
+    /// Height of the document the grid fills
+    pub fn view_height(&self) -> f32 {
+        self.grid.view_height()
+    }
+
+    /// Size of the part of the document the source image is stretched over
+    pub fn image_span(&self) -> (f32, f32) {
+        (self.grid.image_width_units(), self.grid.view_height())
+    }
+
+    /// Samples every cell of the given row, splitting detailed ones if the options ask for it
+    pub fn sample_row(&self, row: usize) -> Vec<(Triangle, [u8; 4])> {
+        let mut cells = Vec::with_capacity(self.grid.n_cols());
+        for cell in self.grid.clone().row(row) {
+            let (min, max) = (cell.pixel_min, cell.pixel_max);
+            match &self.subdivider {
+                Some(subdivider) if subdivider.is_detailed(min, max) => {
+                    subdivider.subdivide(cell.triangle, 1, &mut cells);
+                }
+                _ => {
+                    let at = (cell.img_x, cell.img_y);
+                    if let Some(rgba) = sample_cell(self.source, self.options, at, min, max) {
+                        cells.push((cell.triangle, rgba));
+                    }
+                }
+            }
+        }
+        tick_row(self.options);
+        cells
+    }
+}
+
+/// Number of triangles the image is divided into across each row of the grid over an image
+/// of the given size. Each row has one more, poking half out past the right edge
+pub(crate) fn n_horizontal_triangles(
+    image_width: usize,
+    image_height: usize,
+    n_vertical_tris: usize,
+    options: &RenderOptions,
+) -> usize {
+    let sqrt_3 = (3.0_f32).sqrt();
+
+    // Unless the options pin it down, match the image's aspect ratio. Each triangle
+    // steps the grid by height / sqrt(3), so sqrt(3) triangles span one triangle height
+    let n_horiz_tris = options.horizontal.unwrap_or_else(|| {
+        if options.legacy_aspect {
+            let n_horiz_tris = (image_width * n_vertical_tris) / image_height.max(1);
+            (n_horiz_tris as f32 * sqrt_3) as usize
+        } else {
+            let n_horiz_tris =
+                (image_width * n_vertical_tris) as f32 * sqrt_3 / image_height.max(1) as f32;
+            n_horiz_tris.round() as usize
+        }
+    });
+
+    // Mirrored grids only line up when repeated if they end on the parity they started with
+    let n_horiz_tris = if options.mirror && options.horizontal.is_none() {
+        n_horiz_tris + n_horiz_tris % 2
+    } else {
+        n_horiz_tris
+    };
+
+    // A grid needs at least one triangle to divide by
+    n_horiz_tris.max(1)
+}
+
+/// Number of hexagons across each row of the grid over an image of the given size
+pub(crate) fn n_hex_columns(
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    row_height: f32,
+    options: &RenderOptions,
+) -> usize {
+    let radius = row_height / 1.5;
+    let hex_width = radius * (3.0_f32).sqrt();
+
+    // Match the aspect ratio of the source image, unless the options pin the width down
+    let n_cols = options.horizontal.unwrap_or_else(|| {
+        let n_cols =
+            (image_width * n_rows) as f32 * row_height / (image_height.max(1) as f32 * hex_width);
+        n_cols.round() as usize
+    });
+    n_cols.max(1)
+}
+
+/// Number of squares across each row of the grid over an image of the given size
+pub(crate) fn n_square_columns(
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    options: &RenderOptions,
+) -> usize {
+    // Match the aspect ratio of the source image, unless the options pin the width down
+    let n_cols = options.horizontal.unwrap_or_else(|| {
+        let n_cols = ((image_width * n_rows) as f32 / image_height.max(1) as f32).round();
+        n_cols as usize
+    });
+    n_cols.max(1)
+}
+
+/// Number of diamonds across each row of the grid over an image of the given size
+pub(crate) fn n_diamond_columns(
+    image_width: usize,
+    image_height: usize,
+    n_rows: usize,
+    options: &RenderOptions,
+) -> usize {
+    // Each diamond is two triangle columns, or 2 / sqrt(3) triangle heights, wide
+    let n_cols = options.horizontal.unwrap_or_else(|| {
+        let n_cols =
+            (image_width * n_rows) as f32 * (3.0_f32).sqrt() / (2 * image_height.max(1)) as f32;
+        n_cols.round() as usize
+    });
+    n_cols.max(1)
+}
+
+/// Tiles the image with the alternating triangle grid
+pub(crate) fn tile_triangles(
+    source: &Source,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    if options.orientation == Orientation::Vertical {
+        return tile_triangles_vertical(source, n_vertical_tris, triangle_height, options);
+    }
+
+    let layout = layout_triangles(source, n_vertical_tris, triangle_height, options);
+    let stats = RenderStats::from_cells(&layout.cells);
+
+    let mut document = new_document(layout.view_width, layout.view_height, options);
+    let document = match shadow_group(&mut document, options) {
+        Some(group) => {
+            let group = emit_triangles(group, &layout, options);
+            document.add(emit_region_borders(group, &layout.cells, options))
+        }
+        None => {
+            let document = emit_triangles(document, &layout, options);
+            emit_region_borders(document, &layout.cells, options)
+        }
+    };
+    (document, stats)
+}
+
+/// Tiles the image with the triangle grid turned a quarter. The grid is laid over the
+/// transposed image as usual, then transposed back by swapping x and y in the document
+fn tile_triangles_vertical(
+    source: &Source,
+    n_vertical_tris: usize,
+    triangle_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    let (width, rgb) = transpose_pixels(source.width, source.rgb, 3);
+    let alpha = source
+        .alpha
+        .map(|alpha| transpose_pixels(source.width, alpha, 1).1);
+    let transposed = Source::new(width, &rgb, alpha.as_deref());
+
+    // Flips still refer to the image as it is displayed
+    let options = &RenderOptions {
+        flip_h: options.flip_v,
+        flip_v: options.flip_h,
+        ..options.clone()
+    };
+
+    let layout = layout_triangles(&transposed, n_vertical_tris, triangle_height, options);
+    let stats = RenderStats::from_cells(&layout.cells);
+
+    let group = Group::new().set("transform", "matrix(0 1 1 0 0 0)");
+    let group = emit_triangles(group, &layout, options);
+    let group = emit_region_borders(group, &layout.cells, options);
+
+    let mut document = new_document(layout.view_height, layout.view_width, options);
+    match shadow_group(&mut document, options) {
+        Some(shadow) => document.append(shadow.add(group)),
+        None => document.append(group),
+    }
+    (document, stats)
+}
+
+/// Appends the triangles to the document, or a group within it,
+/// in whichever form the options ask for
+fn emit_triangles<N: Node>(document: N, layout: &TriangleLayout, options: &RenderOptions) -> N {
+    let cells = &layout.cells;
+    if options.gradient {
+        return emit_with_gradients(document, cells, &layout.corner_colors, &options.style);
+    }
+
+    // Every triangle placed from the shared outlines has the same size
+    if options.defs && options.splitting().is_none() {
+        return emit_with_defs(document, cells, &options.style);
+    }
+
+    if options.classes {
+        if options.merge {
+            let regions = merge_regions(cells, &options.style);
+            return emit_outlines_with_classes(document, regions, &options.style);
+        }
+        return emit_with_classes(document, cells, &options.style);
+    }
+
+    if options.layers_by_color {
+        return emit_layers_by_color(document, cells, |group, cells| {
+            emit_plain_triangles(group, cells, options)
+        });
+    }
+
+    emit_plain_triangles(document, cells, options)
+}
+
+/// Appends a single unfilled path over the borders between differently colored triangles,
+/// if the options ask for one
+fn emit_region_borders<N: Node>(
+    mut document: N,
+    cells: &[(Triangle, [u8; 4])],
+    options: &RenderOptions,
+) -> N {
+    if let Some(color) = &options.region_borders {
+        document.append(
+            SvgPath::new()
+                .set("d", region_borders(cells, &options.style))
+                .set("fill", "none")
+                .set("stroke", color.as_str())
+                .set("stroke-width", options.style.stroke_width)
+                .set("stroke-linecap", "round"),
+        );
+    }
+    document
+}
+
+/// Appends the triangles as paths of their own, or merged into one path per region
+fn emit_plain_triangles<N: Node>(
+    mut document: N,
+    cells: &[(Triangle, [u8; 4])],
+    options: &RenderOptions,
+) -> N {
+    if options.merge {
+        for path in merge_by_color(cells, &options.style) {
+            document.append(path);
+        }
+    } else {
+        match options.element {
+            TileElement::Path => {
+                let paths: Vec<_> = cells
+                    .par_iter()
+                    .map(|&(triangle, [r, g, b, a])| {
+                        with_alpha(
+                            triangle.to_path(&encode_color([r, g, b]), &options.style),
+                            a,
+                        )
+                    })
+                    .collect();
+                for path in paths {
+                    document.append(path);
+                }
+            }
+            TileElement::Polygon => {
+                let polygons: Vec<_> = cells
+                    .par_iter()
+                    .map(|&(triangle, [r, g, b, a])| {
+                        with_alpha(
+                            triangle.to_polygon(&encode_color([r, g, b]), &options.style),
+                            a,
+                        )
+                    })
+                    .collect();
+                for polygon in polygons {
+                    document.append(polygon);
+                }
+            }
+        }
+    }
+
+    document
+}
+
+/// Lays out a pointy-top hexagon grid whose rows are `row_height` apart
+pub(crate) fn tile_hexagons(
+    source: &Source,
+    n_rows: usize,
+    row_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    let (image_width, image_height) = (source.width, source.height);
+    let n_rows = n_rows.max(1);
+
+    // Rows of pointy-top hexagons overlap by half a side, so they step by 1.5 radii
+    let radius = row_height / 1.5;
+    let hex_width = radius * (3.0_f32).sqrt();
+
+    let n_cols = n_hex_columns(image_width, image_height, n_rows, row_height, options);
+
+    // Odd rows are shifted right by half a hexagon, and the last row pokes out by half a side
+    let view_width = n_cols as f32 * hex_width + hex_width / 2.;
+    let view_height = n_rows as f32 * row_height + radius / 2.;
+
+    let document = new_document(view_width, view_height, options);
+
+    // Converts document coordinates to fractional source pixels
+    let to_position = |x: f32, y: f32| {
+        (
+            (x.max(0.) * image_width as f32 / view_width) as f64,
+            (y.max(0.) * image_height as f32 / view_height) as f64,
+        )
+    };
+    // Converts document coordinates to the source pixel they land on
+    let to_pixels = |x: f32, y: f32| {
+        let (x, y) = to_position(x, y);
+        (x as usize, y as usize)
+    };
+
+    let mut cells: Vec<_> = (0..n_rows * n_cols)
+        .into_par_iter()
+        .filter_map(|idx| {
+            let (row, col) = (idx / n_cols, idx % n_cols);
+            let offset = if row & 1 == 1 { hex_width / 2. } else { 0. };
+            let cy = radius + row as f32 * row_height;
+            let cx = hex_width / 2. + col as f32 * hex_width + offset;
+            if col + 1 == n_cols {
+                tick_row(options);
+            }
+
+            let rgba = sample_cell(
+                source,
+                options,
+                to_position(cx, cy),
+                to_pixels(cx - hex_width / 2., cy - radius),
+                to_pixels(cx + hex_width / 2., cy + radius),
+            )?;
+
+            Some(((row, col, cx, cy), rgba))
+        })
+        .collect();
+
+    recolor_cells(&mut cells, options, |&(row, col, _, _)| (row, col));
+    let stats = RenderStats::from_cells(&cells);
+
+    let document = emit_shapes(
+        document,
+        &cells,
+        options,
+        |&((_, _, cx, cy), [r, g, b, _])| {
+            hexagon_at(cx, cy, radius, &encode_color([r, g, b]), &options.style)
+        },
+    );
+
+    (document, stats)
+}
+
+/// Lays out a grid of `n_rows` rows of squares with the given side length
+pub(crate) fn tile_squares(
+    source: &Source,
+    n_rows: usize,
+    size: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    let (image_width, image_height) = (source.width, source.height);
+    let n_rows = n_rows.max(1);
+
+    let n_cols = n_square_columns(image_width, image_height, n_rows, options);
+
+    let document = new_document(n_cols as f32 * size, n_rows as f32 * size, options);
+
+    let mut cells: Vec<_> = (0..n_rows * n_cols)
+        .into_par_iter()
+        .filter_map(|idx| {
+            let (row, col) = (idx / n_cols, idx % n_cols);
+            if col + 1 == n_cols {
+                tick_row(options);
+            }
+            let rgba = sample_cell(
+                source,
+                options,
+                (
+                    ((2 * col + 1) * image_width) as f64 / (2 * n_cols) as f64,
+                    ((2 * row + 1) * image_height) as f64 / (2 * n_rows) as f64,
+                ),
+                ((col * image_width) / n_cols, (row * image_height) / n_rows),
+                (
+                    ((col + 1) * image_width) / n_cols,
+                    ((row + 1) * image_height) / n_rows,
+                ),
+            )?;
+
+            Some(((row, col), rgba))
+        })
+        .collect();
+
+    recolor_cells(&mut cells, options, |&position| position);
+    let stats = RenderStats::from_cells(&cells);
+
+    let document = emit_shapes(document, &cells, options, |&((row, col), [r, g, b, _])| {
+        square_at(
+            col as f32 * size,
+            row as f32 * size,
+            size,
+            &encode_color([r, g, b]),
+            &options.style,
+        )
+    });
+
+    (document, stats)
+}
+
+/// Lays out a grid of diamonds, each two triangles `row_height` tall sharing their base,
+/// whose rows are `row_height` apart
+pub(crate) fn tile_diamonds(
+    source: &Source,
+    n_rows: usize,
+    row_height: f32,
+    options: &RenderOptions,
+) -> (svg::Document, RenderStats) {
+    let (image_width, image_height) = (source.width, source.height);
+    let n_rows = n_rows.max(1);
+
+    // Diamonds span two columns of the triangle grid, and two of its rows
+    let half_width = row_height / (3.0_f32).sqrt();
+    let (width, height) = (half_width * 2., row_height * 2.);
+
+    let n_cols = n_diamond_columns(image_width, image_height, n_rows, options);
+
+    // Odd rows are shifted right by half a diamond, and the last row pokes out by half of one
+    let view_width = n_cols as f32 * width + half_width;
+    let view_height = (n_rows + 1) as f32 * row_height;
+
+    let document = new_document(view_width, view_height, options);
+
+    // Converts document coordinates to fractional source pixels
+    let to_position = |x: f32, y: f32| {
+        (
+            (x.max(0.) * image_width as f32 / view_width) as f64,
+            (y.max(0.) * image_height as f32 / view_height) as f64,
+        )
+    };
+    // Converts document coordinates to the source pixel they land on
+    let to_pixels = |x: f32, y: f32| {
+        let (x, y) = to_position(x, y);
+        (x as usize, y as usize)
+    };
+
+    let mut cells: Vec<_> = (0..n_rows * n_cols)
+        .into_par_iter()
+        .filter_map(|idx| {
+            let (row, col) = (idx / n_cols, idx % n_cols);
+            let offset = if row & 1 == 1 { half_width } else { 0. };
+            let x = col as f32 * width + offset;
+            let y = row as f32 * row_height;
+            if col + 1 == n_cols {
+                tick_row(options);
+            }
+
+            let rgba = sample_cell(
+                source,
+                options,
+                to_position(x + half_width, y + row_height),
+                to_pixels(x, y),
+                to_pixels(x + width, y + height),
+            )?;
+
+            Some(((row, col, x, y), rgba))
+        })
+        .collect();
+
+    recolor_cells(&mut cells, options, |&(row, col, _, _)| (row, col));
+    let stats = RenderStats::from_cells(&cells);
+
+    let document = emit_shapes(
+        document,
+        &cells,
+        options,
+        |&((_, _, x, y), [r, g, b, _])| {
+            diamond_at(
+                x,
+                y,
+                width,
+                height,
+                &encode_color([r, g, b]),
+                &options.style,
+            )
+        },
+    );
+
+    (document, stats)
+}
+
+/// Appends a path drawn by `draw` for every cell, grouped by color if the options ask for it,
+/// and within a group casting a shadow if they ask for that
+fn emit_shapes<T: Copy + Sync>(
+    mut document: svg::Document,
+    cells: &[(T, [u8; 4])],
+    options: &RenderOptions,
+    draw: impl Fn(&(T, [u8; 4])) -> SvgPath + Sync,
+) -> svg::Document {
+    match shadow_group(&mut document, options) {
+        Some(group) => document.add(emit_shapes_into(group, cells, options, draw)),
+        None => emit_shapes_into(document, cells, options, draw),
+    }
+}
+
+fn emit_shapes_into<N: Node, T: Copy + Sync>(
+    document: N,
+    cells: &[(T, [u8; 4])],
+    options: &RenderOptions,
+    draw: impl Fn(&(T, [u8; 4])) -> SvgPath + Sync,
+) -> N {
+    if options.layers_by_color {
+        return emit_layers_by_color(document, cells, |group, cells| {
+            append_shapes(group, cells, &draw)
+        });
+    }
+    append_shapes(document, cells, &draw)
+}
+
+fn append_shapes<N: Node, T: Sync>(
+    mut document: N,
+    cells: &[(T, [u8; 4])],
+    draw: &(impl Fn(&(T, [u8; 4])) -> SvgPath + Sync),
+) -> N {
+    let paths: Vec<_> = cells
+        .par_iter()
+        .map(|cell| with_alpha(draw(cell), cell.1[3]))
+        .collect();
+    for path in paths {
+        document.append(path);
+    }
+    document
+}
+
+/// Advances the progress bar, if there is one, past a finished row
+fn tick_row(options: &RenderOptions) {
+    if let Some(progress) = &options.progress {
+        progress.inc(1);
+    }
+}
+
+/// Applies the adjustments that depend on every sampled color at once.
+/// `position` gives the (row, col) of each cell on its grid, for dithering
+fn recolor_cells<T>(
+    cells: &mut [(T, [u8; 4])],
+    options: &RenderOptions,
+    position: impl Fn(&T) -> (usize, usize),
+) {
+    if let (true, Some(palette)) = (options.randomize_colors, &options.palette) {
+        for (cell, rgba) in cells.iter_mut() {
+            let [r, g, b] = random_palette_color(palette, options.style.seed, position(cell));
+            *rgba = [r, g, b, rgba[3]];
+        }
+        return;
+    }
+
+    let mut colors: Vec<[u8; 3]> = cells.iter().map(|(_, [r, g, b, _])| [*r, *g, *b]).collect();
+
+    let quantizer = Quantizer {
+        posterize: options.posterize,
+        palette: match (&options.palette, options.colors) {
+            (Some(palette), _) => Some(palette.clone()),
+            (None, Some(k)) => Some(kmeans_palette(&colors, k)),
+            (None, None) => None,
+        },
+        palette_space: options.palette_space,
+    };
+    if quantizer.is_identity() {
+        return;
+    }
+
+    if options.dither {
+        let positions: Vec<(usize, usize)> = cells.iter().map(|(cell, _)| position(cell)).collect();
+        dither(&mut colors, &positions, &quantizer);
+    } else {
+        for color in &mut colors {
+            *color = quantizer.quantize(*color);
+        }
+    }
+
+    for ((_, rgba), [r, g, b]) in cells.iter_mut().zip(colors) {
+        *rgba = [r, g, b, rgba[3]];
+    }
+}
+
+/// Creates an empty document spanning (0, 0) to (view_width, view_height),
+/// with the background filled in if one was requested
+pub(crate) fn new_document(
+    view_width: f32,
+    view_height: f32,
+    options: &RenderOptions,
+) -> svg::Document {
+    let precision = options.style.precision;
+    let margin = options.margin;
+    // The view box grows outwards, so the tiles keep their coordinates
+    let (view_x, view_y) = if margin > 0. {
+        (round_to(-margin, precision), round_to(-margin, precision))
+    } else {
+        (0., 0.)
+    };
+    let (view_width, view_height) = (
+        round_to(view_width + 2. * margin, precision),
+        round_to(view_height + 2. * margin, precision),
+    );
+    let mut document =
+        svg::Document::new().set("viewBox", (view_x, view_y, view_width, view_height));
+
+    if let Some(physical_width) = &options.physical_width {
+        let (width, height) = physical_width.attributes(view_width, view_height);
+        document.assign("width", width);
+        document.assign("height", height);
+    }
+
+    if let Some(metadata) = &options.metadata {
+        append_metadata(&mut document, metadata);
+    }
+
+    if let Some(background) = &options.background {
+        document.append(
+            Rectangle::new()
+                .set("x", view_x)
+                .set("y", view_y)
+                .set("width", view_width)
+                .set("height", view_height)
+                .set("fill", background.as_str()),
+        );
+    }
+
+    document
+}
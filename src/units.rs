@@ -0,0 +1,67 @@
+use anyhow::{bail, Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Physical unit for the document's width and height attributes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Unit {
+    /// Millimeters
+    #[default]
+    Mm,
+    /// Inches
+    In,
+    /// CSS pixels
+    Px,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Unit::Mm => "mm",
+            Unit::In => "in",
+            Unit::Px => "px",
+        })
+    }
+}
+
+impl FromStr for Unit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mm" => Ok(Unit::Mm),
+            "in" => Ok(Unit::In),
+            "px" => Ok(Unit::Px),
+            other => bail!("Unknown unit {:?} (expected mm, in or px)", other),
+        }
+    }
+}
+
+/// The physical width the document should be displayed or printed at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicalWidth {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl PhysicalWidth {
+    /// Returns the `width` and `height` attributes for a view box of the given size,
+    /// keeping its aspect ratio
+    pub fn attributes(&self, view_width: f32, view_height: f32) -> (String, String) {
+        let height = self.value * view_height / view_width;
+        (
+            format!("{}{}", self.value, self.unit),
+            format!("{}{}", height, self.unit),
+        )
+    }
+
+    /// Returns the width in PostScript points, of which there are 72 to the inch
+    pub fn points(&self) -> f32 {
+        match self.unit {
+            Unit::Mm => self.value * 72. / 25.4,
+            Unit::In => self.value * 72.,
+            // CSS pixels are 96 to the inch
+            Unit::Px => self.value * 0.75,
+        }
+    }
+}
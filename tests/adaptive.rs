@@ -0,0 +1,125 @@
+use dont_tell_avali::{
+    triangle_cells_rgba, triangle_grid_size, AdaptiveOptions, RenderOptions, TileStyle,
+};
+
+/// A `width` x `height` RGBA image that is flat gray on the left half and a checkerboard of
+/// single black and white pixels on the right
+fn half_checkerboard(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height)
+        .flat_map(|idx| {
+            let (x, y) = (idx % width, idx / width);
+            let value = if x < width / 2 {
+                128
+            } else if (x + y) % 2 == 0 {
+                0
+            } else {
+                255
+            };
+            [value, value, value, 255]
+        })
+        .collect()
+}
+
+/// Options splitting triangles over any detail at all, at most `max_depth` times
+fn split_everywhere(max_depth: usize) -> RenderOptions {
+    RenderOptions {
+        adaptive: Some(AdaptiveOptions {
+            threshold: 0.,
+            max_depth,
+        }),
+        ..RenderOptions::default()
+    }
+}
+
+#[test]
+fn flat_image_is_never_split() {
+    let rgba = [90, 120, 150, 255].repeat(32 * 32);
+    let options = split_everywhere(4);
+    let cells = triangle_cells_rgba(32, &rgba, 4, 0.1, &options);
+
+    let (n_cols, n_rows) = triangle_grid_size(32, 32, 4, &options);
+    assert_eq!(cells.len(), n_cols * n_rows);
+    assert!(cells.iter().all(|(triangle, _)| triangle.height == 0.1));
+}
+
+#[test]
+fn checkerboard_is_split_to_the_maximum_depth() {
+    // Rows 32 pixels tall, so even the smallest triangles cover several squares
+    let rgba: Vec<u8> = (0..64 * 64)
+        .flat_map(|idx| {
+            let value = if (idx % 64 + idx / 64) % 2 == 0 {
+                0
+            } else {
+                255
+            };
+            [value, value, value, 255]
+        })
+        .collect();
+    for max_depth in 1..=3 {
+        let options = split_everywhere(max_depth);
+        let cells = triangle_cells_rgba(64, &rgba, 2, 0.1, &options);
+
+        let (n_cols, n_rows) = triangle_grid_size(64, 64, 2, &options);
+        let finest = 0.1 / (1 << max_depth) as f32;
+        assert_eq!(cells.len(), n_cols * n_rows * 4usize.pow(max_depth as u32));
+        assert!(cells.iter().all(|(triangle, _)| triangle.height == finest));
+    }
+}
+
+/// Reads the corners out of a triangle path's data, as written by `Triangle::to_path`
+fn path_corners(path: &str) -> Vec<(f32, f32)> {
+    let data = path
+        .split("d=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    data.split(|c: char| c.is_ascii_alphabetic() || c.is_whitespace())
+        .filter(|point| !point.is_empty())
+        .map(|point| {
+            let (x, y) = point.split_once(',').unwrap();
+            (x.parse().unwrap(), y.parse().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn jitter_moves_shared_corners_of_split_triangles_together() {
+    let rgba = half_checkerboard(32, 32);
+    let options = RenderOptions {
+        adaptive: Some(AdaptiveOptions {
+            threshold: 100.,
+            max_depth: 2,
+        }),
+        ..RenderOptions::default()
+    };
+    let style = TileStyle {
+        jitter: 0.01,
+        precision: None,
+        ..TileStyle::default()
+    };
+    let cells = triangle_cells_rgba(32, &rgba, 4, 0.1, &options);
+    let sizes = cells.iter().map(|(triangle, _)| triangle.height.to_bits());
+    assert!(sizes.collect::<std::collections::BTreeSet<_>>().len() > 1);
+
+    // Every triangle meeting at a corner of the unjittered grid must draw it in one place
+    let mut moved = std::collections::HashMap::new();
+    for (triangle, _) in &cells {
+        let path = triangle.to_path("black", &style).to_string();
+        for (corner, jittered) in triangle.corners().iter().zip(path_corners(&path)) {
+            let key = (
+                (corner.0 * 1e4).round() as i64,
+                (corner.1 * 1e4).round() as i64,
+            );
+            let first = *moved.entry(key).or_insert(jittered);
+            assert!(
+                (first.0 - jittered.0).abs() < 1e-5 && (first.1 - jittered.1).abs() < 1e-5,
+                "corner {:?} is drawn at both {:?} and {:?}",
+                corner,
+                first,
+                jittered
+            );
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use dont_tell_avali::{build_document, view_box_size};
+
+/// Builds a mosaic of a flat gray `width` x `height` image and returns its aspect ratio
+fn mosaic_aspect(width: usize, height: usize, n_vertical_tris: usize) -> f32 {
+    let image_data = vec![128; width * height * 3];
+    let document = build_document(width, &image_data, n_vertical_tris, 0.1);
+    let (view_width, view_height) = view_box_size(&document).unwrap();
+    view_width / view_height
+}
+
+/// Checks that the mosaic matches the image's aspect ratio to within one triangle step
+fn assert_aspect_matches(width: usize, height: usize, n_vertical_tris: usize) {
+    let expected = width as f32 / height as f32;
+    let actual = mosaic_aspect(width, height, n_vertical_tris);
+
+    // Rounding to whole triangles can be off by up to half a step of height / sqrt(3)
+    let tolerance = 0.5 / (3.0_f32).sqrt() / n_vertical_tris as f32;
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{}x{} image with {} rows has aspect ratio {}, expected {}",
+        width,
+        height,
+        n_vertical_tris,
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn square_image_gives_square_mosaic() {
+    assert_aspect_matches(40, 40, 4);
+    assert_aspect_matches(40, 40, 30);
+}
+
+#[test]
+fn wide_image_keeps_its_aspect_ratio() {
+    assert_aspect_matches(64, 32, 5);
+    assert_aspect_matches(300, 100, 30);
+}
+
+#[test]
+fn tall_image_keeps_its_aspect_ratio() {
+    assert_aspect_matches(32, 64, 7);
+    assert_aspect_matches(100, 300, 30);
+}
@@ -0,0 +1,28 @@
+use dont_tell_avali::gaussian_blur_rgb;
+
+#[test]
+fn zero_sigma_skips_blur() {
+    let data: Vec<u8> = (0..4 * 3 * 3).map(|i| (i * 7) as u8).collect();
+    assert_eq!(gaussian_blur_rgb(4, &data, 0.), data);
+}
+
+#[test]
+fn blur_spreads_a_dot_evenly() {
+    // A white dot in the middle of a black 5x5 image
+    let mut data = vec![0; 5 * 5 * 3];
+    data[(2 * 5 + 2) * 3..][..3].copy_from_slice(&[255; 3]);
+    let blurred = gaussian_blur_rgb(5, &data, 1.);
+    let at = |x: usize, y: usize| blurred[(y * 5 + x) * 3];
+
+    assert!(at(2, 2) < 255 && at(2, 2) > at(1, 2));
+    assert_eq!(at(1, 2), at(3, 2));
+    assert_eq!(at(2, 1), at(2, 3));
+    assert_eq!(at(1, 2), at(2, 1));
+    assert!(at(0, 0) > 0);
+}
+
+#[test]
+fn blur_keeps_flat_images() {
+    let data = [10, 120, 250].repeat(6 * 4);
+    assert_eq!(gaussian_blur_rgb(6, &data, 2.5), data);
+}
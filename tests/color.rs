@@ -0,0 +1,75 @@
+use dont_tell_avali::{adjust, apply_hsl, encode_color, triangle_at, ColorOptions, TileStyle};
+
+#[test]
+fn contrast_scales_before_brightness_offsets() {
+    // (138 - 128) * 2 + 128 + 10, where offsetting first would give 168
+    assert_eq!(adjust([138; 3], 10., 2.), [158; 3]);
+}
+
+#[test]
+fn adjustments_apply_in_order() {
+    let options = ColorOptions {
+        brightness: 100.,
+        threshold: Some(128),
+        invert: true,
+        ..ColorOptions::default()
+    };
+    // Brightened to 150, which reaches the threshold, so white, inverted to black.
+    // Inverting before the threshold would give white
+    assert_eq!(options.apply([50; 3]), [0; 3]);
+
+    let options = ColorOptions {
+        saturation: 0.,
+        grayscale: true,
+        threshold: Some(100),
+        ..ColorOptions::default()
+    };
+    // Desaturating pure blue leaves the gray of its HSL lightness, 128, which reaches the
+    // threshold. Its own luma is only 29, so taking the grayscale first would give black
+    assert_eq!(options.apply([0, 0, 255]), [255; 3]);
+}
+
+#[test]
+fn default_adjustments_keep_colors() {
+    let options = ColorOptions::default();
+    for rgb in [[0, 0, 0], [12, 200, 77], [255, 255, 255]] {
+        assert_eq!(options.apply(rgb), rgb);
+    }
+}
+
+#[test]
+fn hue_wraps_around() {
+    let red = [255, 0, 0];
+    assert_eq!(apply_hsl(red, 120., 1.), [0, 255, 0]);
+    assert_eq!(apply_hsl(red, 480., 1.), [0, 255, 0]);
+    assert_eq!(apply_hsl(red, -120., 1.), [0, 0, 255]);
+    assert_eq!(apply_hsl(red, 360., 1.), red);
+    assert_eq!(apply_hsl(red, -720., 1.), red);
+}
+
+/// Outline of a triangle with corners off the grid of round numbers
+fn outline(precision: Option<u8>) -> String {
+    let style = TileStyle {
+        precision,
+        ..TileStyle::default()
+    };
+    let path = triangle_at(1.23456, 0.5, 0.75, 2.98765, true, "#000", &style);
+    let path = path.to_string();
+    path.split('"').nth(1).unwrap().to_string()
+}
+
+#[test]
+fn coordinates_round_to_precision() {
+    // Halves round away from zero
+    assert_eq!(outline(Some(0)), "M1,1 l-1,3 l2,0 z");
+    assert_eq!(outline(Some(2)), "M1.23,0.5 l-0.75,2.99 l1.5,0 z");
+    assert_eq!(outline(None), "M1.23456,0.5 l-0.75,2.98765 l1.5,0 z");
+}
+
+#[test]
+fn colors_use_shorthand_when_digits_repeat() {
+    assert_eq!(encode_color([0xAA, 0xBB, 0xCC]), "#ABC");
+    assert_eq!(encode_color([0, 0xFF, 0x11]), "#0F1");
+    assert_eq!(encode_color([0xAA, 0xBB, 0xCD]), "#AABBCD");
+    assert_eq!(encode_color([0x01, 0x23, 0x45]), "#012345");
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::encode_png;
+use png::ColorType;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Writes a 4x4 test image and a settings file with the given name and contents to a fresh
+/// directory, returning the directory and the paths of both
+fn fixture(test: &str, name: &str, settings: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("config-test-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("in.png");
+    let data: Vec<u8> = (0..48).map(|i| (i * 5) as u8).collect();
+    std::fs::write(&image, encode_png(4, 4, ColorType::Rgb, &data)).unwrap();
+    let config = dir.join(name);
+    std::fs::write(&config, settings.replace("IMAGE", &image.to_string_lossy())).unwrap();
+    (dir, image, config)
+}
+
+/// Runs the tool with the given settings file and arguments
+fn run(config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dont_tell_avali"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn command_line_takes_precedence() {
+    let (dir, _, config) = fixture("precedence", "c.toml", "input = 'IMAGE'\nvertical = 4\n");
+    let from_file = run(&config, &["--dry-run"]);
+    let overridden = run(&config, &["--dry-run", "--vertical", "2"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(from_file.status.success());
+    assert!(String::from_utf8_lossy(&from_file.stdout).starts_with("Would write 32 triangles"));
+    assert!(overridden.status.success());
+    assert!(String::from_utf8_lossy(&overridden.stdout).starts_with("Would write 8 triangles"));
+}
+
+#[test]
+fn json_settings_are_read() {
+    let (dir, _, config) = fixture("json", "c.json", r#"{"input": "IMAGE", "vertical": 2}"#);
+    let output = run(&config, &["--dry-run"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Would write 8 triangles"));
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let (dir, _, config) = fixture("unknown", "c.toml", "input = 'IMAGE'\nvertcal = 4\n");
+    let output = run(&config, &["--dry-run"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown field `vertcal`"));
+}
+
+#[test]
+fn switches_can_be_turned_off() {
+    let settings = "input = 'IMAGE'\nvertical = 2\nquiet = true\n";
+    let (dir, _, config) = fixture("switches", "c.toml", settings);
+    let out = dir.join("out.svg");
+    let out = out.to_str().unwrap();
+    let quiet = run(&config, &["-o", out]);
+    let loud = run(&config, &["-o", out, "--quiet=false"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(quiet.status.success());
+    assert!(quiet.stderr.is_empty());
+    assert!(loud.status.success());
+    assert!(String::from_utf8_lossy(&loud.stderr).starts_with("Wrote 8 triangles"));
+}
+
+#[test]
+fn settings_are_checked_against_flags() {
+    let (dir, _, config) = fixture("conflicts", "c.toml", "input = 'IMAGE'\nmerge = true\n");
+    let conflicting = run(&config, &["--dry-run", "--defs"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!conflicting.status.success());
+    assert!(
+        String::from_utf8_lossy(&conflicting.stderr).contains("--defs can't be used with --merge")
+    );
+}
+
+#[test]
+fn preset_is_checked_against_flags() {
+    let (dir, _, config) = fixture("preset", "c.toml", "input = 'IMAGE'\n");
+    let defs = run(&config, &["--dry-run", "--preset", "cel", "--defs"]);
+    let stream = run(&config, &["--dry-run", "--preset", "cel", "--stream"]);
+    let classes = run(&config, &["--dry-run", "--preset", "cel", "--classes"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The preset merges tiles, which neither defs nor streaming can do
+    assert!(!defs.status.success());
+    assert!(String::from_utf8_lossy(&defs.stderr).contains("--defs can't be used with --merge"));
+    assert!(!stream.status.success());
+    assert!(String::from_utf8_lossy(&stream.stderr).contains("--stream can't be used with --merge"));
+    assert!(classes.status.success());
+}
+
+/// Reads the number of tiles out of a dry run's report
+fn tiles_written(output: &Output) -> usize {
+    let report = String::from_utf8_lossy(&output.stdout);
+    let count = report
+        .strip_prefix("Would write ")
+        .unwrap()
+        .split(' ')
+        .next();
+    count.unwrap().parse().unwrap()
+}
+
+#[test]
+fn tile_count_limits_the_grid() {
+    let settings = "input = 'IMAGE'\nvertical = 40\nquiet = true\n";
+    let (dir, _, config) = fixture("max-triangles", "c.toml", settings);
+    let too_few = run(&config, &["--dry-run", "--max-triangles", "1"]);
+    let few_tiles: Vec<Output> = [6, 10, 27]
+        .iter()
+        .map(|max| run(&config, &["--dry-run", "--max-triangles", &max.to_string()]))
+        .collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Not even one row fits a single tile
+    assert!(!too_few.status.success());
+    for (output, max) in few_tiles.iter().zip([6, 10, 27]) {
+        assert!(output.status.success());
+        let tiles = tiles_written(output);
+        assert!(tiles <= max, "{} tiles for at most {}", tiles, max);
+    }
+}
+
+#[test]
+fn row_height_limits_the_grid() {
+    let settings = "input = 'IMAGE'\nvertical = 40\nquiet = true\n";
+    let (dir, _, config) = fixture("min-height", "c.toml", settings);
+    let short_rows = run(&config, &["--dry-run", "--min-height", "2"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The 4 pixel tall image only has room for two rows
+    assert!(short_rows.status.success());
+    assert_eq!(tiles_written(&short_rows), 8);
+}
@@ -0,0 +1,74 @@
+use dont_tell_avali::{
+    build_document, build_document_rgba, stream_document, tile_grid_size, tiled_size,
+    AdaptiveOptions, CropRect, Orientation, RenderOptions, Shape,
+};
+
+/// Options covering each way of laying out and sampling tiles
+fn layouts() -> Vec<RenderOptions> {
+    let mut layouts = vec![];
+    for shape in [Shape::Triangle, Shape::Hex, Shape::Square, Shape::Diamond] {
+        for orientation in [Orientation::Horizontal, Orientation::Vertical] {
+            layouts.push(RenderOptions {
+                shape,
+                orientation,
+                ..Default::default()
+            });
+        }
+    }
+    layouts.push(RenderOptions {
+        adaptive: Some(AdaptiveOptions {
+            threshold: 0.,
+            max_depth: 3,
+        }),
+        ..Default::default()
+    });
+    layouts.push(RenderOptions {
+        supersample: Some(3),
+        flip_h: true,
+        flip_v: true,
+        ..Default::default()
+    });
+    layouts
+}
+
+#[test]
+fn empty_images_give_empty_mosaics() {
+    // No pixels at all, a width with no rows, and rows with no width
+    let images = [(0, vec![]), (4, vec![]), (0, vec![255; 48])];
+    for (width, rgba) in &images {
+        let document = build_document(*width, &rgba[..rgba.len() * 3 / 4], 5, 0.1);
+        assert!(!document.to_string().contains("<path"));
+
+        for options in layouts() {
+            let document = build_document_rgba(*width, rgba, 5, 0.1, &options);
+            assert!(!document.to_string().contains("<path"));
+        }
+
+        let mut streamed = vec![];
+        stream_document(&mut streamed, *width, rgba, 5, 0.1, &Default::default()).unwrap();
+        assert!(!String::from_utf8(streamed).unwrap().contains("<path"));
+    }
+}
+
+#[test]
+fn empty_images_have_a_grid_size() {
+    let options = RenderOptions {
+        crop: Some(CropRect {
+            x: 10,
+            y: 10,
+            width: 4,
+            height: 4,
+        }),
+        ..Default::default()
+    };
+    assert_eq!(tiled_size(0, 0, &options), (0, 0));
+    for shape in [Shape::Triangle, Shape::Hex, Shape::Square, Shape::Diamond] {
+        let options = RenderOptions {
+            shape,
+            ..options.clone()
+        };
+        let (columns, rows) = tile_grid_size(0, 0, 5, 0.1, &options);
+        assert!((1..=10).contains(&columns), "{} columns", columns);
+        assert_eq!(rows, 5);
+    }
+}
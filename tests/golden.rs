@@ -0,0 +1,439 @@
+mod common;
+
+use common::encode_png;
+use dont_tell_avali::{
+    build_animated_document, build_document_rgba, load_png_rgba, render_svg_from_bytes,
+    stream_document, triangle_cells_rgba, write_csv, write_dxf, write_json, DropShadow,
+    RenderOptions, Shape, TileStyle, Triangle,
+};
+use png::ColorType;
+
+/// Decodes the PNG and renders it with `n_vertical` rows of triangles
+fn render(png: &[u8], n_vertical: usize, options: &RenderOptions) -> String {
+    let (width, rgba) = load_png_rgba(png).unwrap();
+    build_document_rgba(width, &rgba, n_vertical, 1., options).to_string()
+}
+
+/// Lays out one row of triangles over a 4x2 image whose first three triangles are red, blue
+/// and red, and whose last, half past the edge, is black, for the exporters that take the
+/// triangles themselves
+fn red_blue_red() -> Vec<(Triangle, [u8; 4])> {
+    #[rustfmt::skip]
+    let data = [
+        255, 0, 0,   0, 0, 255,   255, 0, 0,   0, 0, 0,
+        255, 0, 0,   0, 0, 255,   255, 0, 0,   0, 0, 0,
+    ];
+    let png = encode_png(4, 2, ColorType::Rgb, &data);
+    let (width, rgba) = load_png_rgba(&png[..]).unwrap();
+    triangle_cells_rgba(width, &rgba, 1, 1., &RenderOptions::default())
+}
+
+/// Two rows, so both rows start with a differently pointing triangle
+#[test]
+fn rgb_two_rows() {
+    #[rustfmt::skip]
+    let data = [
+        255, 0, 0,   0, 255, 0,   0, 0, 255,   255, 255, 255,
+        255, 0, 0,   0, 255, 0,   0, 0, 255,   255, 255, 255,
+        0, 0, 0,     18, 52, 86,  0, 0, 0,     18, 52, 86,
+        0, 0, 0,     18, 52, 86,  0, 0, 0,     18, 52, 86,
+    ];
+    let png = encode_png(4, 4, ColorType::Rgb, &data);
+    let expected = r##"<svg viewBox="0 0 1.7321 2" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#F00" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#0F0" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#00F" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#FFF" stroke="none" stroke-width="0.001"/>
+<path d="M0,1 l-0.5774,1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,2 l-0.5774,-1 l1.1547,0 z" fill="#123456" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,2 l-0.5774,-1 l1.1547,0 z" fill="#123456" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 2, &RenderOptions::default()), expected);
+}
+
+#[test]
+fn rgba_keeps_alpha() {
+    #[rustfmt::skip]
+    let data = [
+        255, 0, 0, 255,   0, 255, 0, 128,   0, 0, 255, 0,   255, 255, 255, 255,
+        255, 0, 0, 255,   0, 255, 0, 128,   0, 0, 255, 0,   255, 255, 255, 255,
+    ];
+    let png = encode_png(4, 2, ColorType::Rgba, &data);
+    let options = RenderOptions {
+        keep_alpha: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#F00" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#0F0" fill-opacity="0.5019608" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#00F" fill-opacity="0" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#FFF" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 1, &options), expected);
+}
+
+#[test]
+fn grayscale() {
+    let data = [0, 100, 200, 255, 0, 100, 200, 255];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#646464" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#FFF" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 1, &RenderOptions::default()), expected);
+}
+
+#[test]
+fn grayscale_alpha() {
+    let data = [
+        0, 255, 100, 51, 200, 0, 255, 255, 0, 255, 100, 51, 200, 0, 255, 255,
+    ];
+    let png = encode_png(4, 2, ColorType::GrayscaleAlpha, &data);
+    let options = RenderOptions {
+        keep_alpha: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#646464" fill-opacity="0.2" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#C8C8C8" fill-opacity="0" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#FFF" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 1, &options), expected);
+}
+
+#[test]
+fn from_bytes_matches_document() {
+    let data = [0, 100, 200, 255, 0, 100, 200, 255];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    assert_eq!(
+        render_svg_from_bytes(&png, 1, 1.).unwrap(),
+        render(&png, 1, &RenderOptions::default())
+    );
+}
+
+#[test]
+fn streamed_matches_document() {
+    #[rustfmt::skip]
+    let data = [
+        255, 0, 0, 255,   0, 255, 0, 128,   0, 0, 255, 0,   255, 255, 255, 255,
+        255, 0, 0, 255,   0, 255, 0, 128,   0, 0, 255, 0,   255, 255, 255, 255,
+        0, 0, 0, 255,     18, 52, 86, 255,  0, 0, 0, 255,   18, 52, 86, 255,
+        0, 0, 0, 255,     18, 52, 86, 255,  0, 0, 0, 255,   18, 52, 86, 255,
+    ];
+    let png = encode_png(4, 4, ColorType::Rgba, &data);
+    let options = RenderOptions {
+        keep_alpha: true,
+        posterize: Some(2),
+        margin: 0.5,
+        background: Some("white".into()),
+        ..Default::default()
+    };
+
+    let (width, rgba) = load_png_rgba(&png[..]).unwrap();
+    let mut streamed = vec![];
+    stream_document(&mut streamed, width, &rgba, 3, 1., &options).unwrap();
+    assert_eq!(
+        String::from_utf8(streamed).unwrap(),
+        render(&png, 3, &options)
+    );
+}
+
+/// Borders are only found once every tile is known, so streaming refuses them rather than
+/// leaving them out
+#[test]
+fn region_borders_are_not_streamed() {
+    let options = RenderOptions {
+        region_borders: Some("black".into()),
+        ..Default::default()
+    };
+    let mut streamed = vec![];
+    let result = stream_document(&mut streamed, 2, &[0; 2 * 2 * 4], 1, 1., &options);
+    assert!(result.is_err());
+}
+
+#[test]
+fn animated_frames() {
+    let first = [0, 100, 200, 255, 0, 100, 200, 255];
+    let second = [0, 100, 0, 255, 0, 100, 0, 255];
+    let frames: Vec<_> = [first, second]
+        .iter()
+        .map(|data| load_png_rgba(&encode_png(4, 2, ColorType::Grayscale, data)[..]).unwrap())
+        .collect();
+    let document = build_animated_document(&frames, 1, 1., 4., &RenderOptions::default()).unwrap();
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#646464" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001">
+<animate attributeName="fill" calcMode="discrete" dur="0.5s" repeatCount="indefinite" values="#C8C8C8;#000"/>
+</path>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#FFF" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(document.to_string(), expected);
+}
+
+#[test]
+fn region_borders_between_colors() {
+    let data = [0, 0, 200, 200, 0, 0, 200, 200];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let options = RenderOptions {
+        region_borders: Some("#222".into()),
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 L1.1547,1" fill="none" stroke="#222" stroke-linecap="round" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 1, &options), expected);
+}
+
+#[test]
+fn diamond_grid() {
+    let data = [0, 0, 200, 200, 0, 0, 200, 200];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let options = RenderOptions {
+        shape: Shape::Diamond,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 2.8868 2" xmlns="http://www.w3.org/2000/svg">
+<path d="M0.5774,0 L1.1547,1 L0.5774,2 L0,1 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 L2.3094,1 L1.7321,2 L1.1547,1 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&png, 1, &options), expected);
+}
+
+#[test]
+fn shadow_wraps_tiles() {
+    let data = [0, 0, 200, 200, 0, 0, 200, 200];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let options = RenderOptions {
+        shadow: Some(DropShadow {
+            dx: 0.1,
+            dy: 0.2,
+            blur: 0.05,
+            color: "#333".into(),
+        }),
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 1" xmlns="http://www.w3.org/2000/svg">
+<defs>
+<filter id="shadow">
+<feDropShadow dx="0.1" dy="0.2" flood-color="#333" stdDeviation="0.05"/>
+</filter>
+</defs>
+<g filter="url(#shadow)">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z" fill="#C8C8C8" stroke="none" stroke-width="0.001"/>
+</g>
+</svg>"##;
+    assert_eq!(render(&png, 1, &options), expected);
+}
+
+#[test]
+fn randomized_colors_fill_dark_tiles_from_palette() {
+    let data = [0, 0, 200, 200, 0, 0, 200, 200];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let palette = vec![[255, 0, 0], [0, 0, 255]];
+    let options = |seed| RenderOptions {
+        palette: Some(palette.clone()),
+        randomize_colors: true,
+        style: TileStyle {
+            seed,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let svg = render(&png, 1, &options(7));
+    assert_eq!(svg, render(&png, 1, &options(7)));
+    // The light third triangle is left out
+    assert_eq!(svg.matches("<path").count(), 2);
+    let from_palette = svg.matches("fill=\"#F00\"").count() + svg.matches("fill=\"#00F\"").count();
+    assert_eq!(from_palette, 2);
+}
+
+#[test]
+fn gradient_through_corner_colors() {
+    let data = [0, 0, 200, 200, 0, 0, 200, 200];
+    let png = encode_png(4, 2, ColorType::Grayscale, &data);
+    let options = RenderOptions {
+        gradient: true,
+        ..Default::default()
+    };
+    let svg = render(&png, 1, &options);
+
+    // The middle triangle's corners straddle the edge between the colors
+    assert!(svg.contains(r##"<path d="M0.5774,0 l-0.5774,1 l1.1547,0 z" fill="url(#gradient-1)""##));
+    assert!(svg.contains(r##"<stop offset="0" stop-color="#000"/>"##));
+    assert!(svg.contains(r##"<stop offset="1" stop-color="#C8C8C8"/>"##));
+}
+
+/// One layer per distinct color, and every y negated since DXF's y axis points up
+#[test]
+fn dxf_layers_by_color() {
+    let mut dxf = vec![];
+    write_dxf(&mut dxf, &red_blue_red()).unwrap();
+    #[rustfmt::skip]
+    let expected = [
+        "0", "SECTION", "2", "HEADER", "9", "$ACADVER", "1", "AC1015", "0", "ENDSEC",
+        "0", "SECTION", "2", "TABLES", "0", "TABLE", "2", "LAYER", "70", "3",
+        "0", "LAYER", "2", "COLOR_FF0000", "70", "0", "62", "7", "420", "16711680",
+        "6", "CONTINUOUS",
+        "0", "LAYER", "2", "COLOR_0000FF", "70", "0", "62", "7", "420", "255",
+        "6", "CONTINUOUS",
+        "0", "LAYER", "2", "COLOR_000000", "70", "0", "62", "7", "420", "0",
+        "6", "CONTINUOUS",
+        "0", "ENDTAB", "0", "ENDSEC",
+        "0", "SECTION", "2", "ENTITIES",
+        "0", "LWPOLYLINE", "8", "COLOR_FF0000", "90", "3", "70", "1",
+        "10", "0", "20", "-1", "10", "-0.57735026", "20", "0", "10", "0.57735026", "20", "0",
+        "0", "LWPOLYLINE", "8", "COLOR_0000FF", "90", "3", "70", "1",
+        "10", "0.57735026", "20", "0", "10", "0", "20", "-1", "10", "1.1547005", "20", "-1",
+        "0", "LWPOLYLINE", "8", "COLOR_FF0000", "90", "3", "70", "1",
+        "10", "1.1547005", "20", "-1", "10", "0.57735026", "20", "0", "10", "1.7320508", "20", "0",
+        "0", "LWPOLYLINE", "8", "COLOR_000000", "90", "3", "70", "1",
+        "10", "1.7320508", "20", "0", "10", "1.1547005", "20", "-1", "10", "2.309401", "20", "-1",
+        "0", "ENDSEC", "0", "EOF",
+    ];
+    let lines: Vec<&str> = std::str::from_utf8(&dxf).unwrap().lines().collect();
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn json_fields() {
+    let mut json = vec![];
+    write_json(&mut json, &red_blue_red()).unwrap();
+    let expected = r#"[{"x":0.0,"y":0.0,"points_up":false,"rgb":[255,0,0]},{"x":0.57735026,"y":0.0,"points_up":true,"rgb":[0,0,255]},{"x":1.1547005,"y":0.0,"points_up":false,"rgb":[255,0,0]},{"x":1.7320508,"y":0.0,"points_up":true,"rgb":[0,0,0]}]
+"#;
+    assert_eq!(String::from_utf8(json).unwrap(), expected);
+}
+
+#[test]
+fn csv_columns() {
+    let mut csv = vec![];
+    write_csv(&mut csv, &red_blue_red()).unwrap();
+    let expected = "row,col,centroid_x,centroid_y,points_up,hex
+0,0,0,0.33333334,false,#F00
+0,1,0.57735026,0.6666667,true,#00F
+0,2,1.1547005,0.33333334,false,#F00
+0,3,1.7320508,0.6666667,true,#000
+";
+    assert_eq!(String::from_utf8(csv).unwrap(), expected);
+}
+
+/// A 4x4 image, red but for a blue top right corner and a black last column, giving two
+/// rows of triangles with only one of the six inside the edge blue
+fn red_with_blue_corner() -> Vec<u8> {
+    #[rustfmt::skip]
+    let data = [
+        255, 0, 0,   255, 0, 0,   0, 0, 255,   0, 0, 0,
+        255, 0, 0,   255, 0, 0,   0, 0, 255,   0, 0, 0,
+        255, 0, 0,   255, 0, 0,   255, 0, 0,   0, 0, 0,
+        255, 0, 0,   255, 0, 0,   255, 0, 0,   0, 0, 0,
+    ];
+    encode_png(4, 4, ColorType::Rgb, &data)
+}
+
+#[test]
+fn merged_neighbors_share_a_path() {
+    let options = RenderOptions {
+        merge: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 2" xmlns="http://www.w3.org/2000/svg">
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z M0.5774,0 l-0.5774,1 l1.1547,0 z M0,1 l-0.5774,1 l1.1547,0 z M0.5774,2 l-0.5774,-1 l1.1547,0 z M1.1547,1 l-0.5774,1 l1.1547,0 z" fill="#F00" stroke="none" stroke-width="0.001"/>
+<path d="M1.1547,1 l-0.5774,-1 l1.1547,0 z" fill="#00F" stroke="none" stroke-width="0.001"/>
+<path d="M1.7321,0 l-0.5774,1 l1.1547,0 z M1.7321,2 l-0.5774,-1 l1.1547,0 z" fill="#000" stroke="none" stroke-width="0.001"/>
+</svg>"##;
+    assert_eq!(render(&red_with_blue_corner(), 2, &options), expected);
+}
+
+#[test]
+fn merged_regions_apart_stay_apart() {
+    let options = RenderOptions {
+        merge: true,
+        ..Default::default()
+    };
+    let document = build_document_rgba(
+        4,
+        &[255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 0, 255].repeat(2),
+        1,
+        1.,
+        &options,
+    )
+    .to_string();
+    assert_eq!(document.matches(r##"fill="#F00""##).count(), 2);
+    assert_eq!(document.matches(r##"fill="#00F""##).count(), 1);
+}
+
+#[test]
+fn classes_one_per_color() {
+    let options = RenderOptions {
+        classes: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 2" xmlns="http://www.w3.org/2000/svg">
+<style>
+path{stroke:none;stroke-width:0.001}.c0{fill:#F00}.c1{fill:#00F}.c2{fill:#000}
+</style>
+<path class="c0" d="M0,1 l-0.5774,-1 l1.1547,0 z"/>
+<path class="c0" d="M0.5774,0 l-0.5774,1 l1.1547,0 z"/>
+<path class="c1" d="M1.1547,1 l-0.5774,-1 l1.1547,0 z"/>
+<path class="c2" d="M1.7321,0 l-0.5774,1 l1.1547,0 z"/>
+<path class="c0" d="M0,1 l-0.5774,1 l1.1547,0 z"/>
+<path class="c0" d="M0.5774,2 l-0.5774,-1 l1.1547,0 z"/>
+<path class="c0" d="M1.1547,1 l-0.5774,1 l1.1547,0 z"/>
+<path class="c2" d="M1.7321,2 l-0.5774,-1 l1.1547,0 z"/>
+</svg>"##;
+    assert_eq!(render(&red_with_blue_corner(), 2, &options), expected);
+}
+
+#[test]
+fn merged_classes_one_path_per_region() {
+    let options = RenderOptions {
+        merge: true,
+        classes: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 2" xmlns="http://www.w3.org/2000/svg">
+<style>
+path{stroke:none;stroke-width:0.001}.c0{fill:#F00}.c1{fill:#00F}.c2{fill:#000}
+</style>
+<path class="c0" d="M0,1 l-0.5774,-1 l1.1547,0 z M0.5774,0 l-0.5774,1 l1.1547,0 z M0,1 l-0.5774,1 l1.1547,0 z M0.5774,2 l-0.5774,-1 l1.1547,0 z M1.1547,1 l-0.5774,1 l1.1547,0 z"/>
+<path class="c1" d="M1.1547,1 l-0.5774,-1 l1.1547,0 z"/>
+<path class="c2" d="M1.7321,0 l-0.5774,1 l1.1547,0 z M1.7321,2 l-0.5774,-1 l1.1547,0 z"/>
+</svg>"##;
+    assert_eq!(render(&red_with_blue_corner(), 2, &options), expected);
+}
+
+/// Each direction of triangle is defined once, and every tile uses one in its own color
+#[test]
+fn defs_one_per_direction() {
+    let options = RenderOptions {
+        defs: true,
+        ..Default::default()
+    };
+    let expected = r##"<svg viewBox="0 0 1.7321 2" xmlns="http://www.w3.org/2000/svg">
+<defs>
+<path d="M0,0 l-0.5774,1 l1.1547,0 z" id="up" stroke="none" stroke-width="0.001"/>
+<path d="M0,1 l-0.5774,-1 l1.1547,0 z" id="down" stroke="none" stroke-width="0.001"/>
+</defs>
+<use fill="#F00" href="#down" x="0" y="0"/>
+<use fill="#F00" href="#up" x="0.5774" y="0"/>
+<use fill="#00F" href="#down" x="1.1547" y="0"/>
+<use fill="#000" href="#up" x="1.7321" y="0"/>
+<use fill="#F00" href="#up" x="0" y="1"/>
+<use fill="#F00" href="#down" x="0.5774" y="1"/>
+<use fill="#F00" href="#up" x="1.1547" y="1"/>
+<use fill="#000" href="#down" x="1.7321" y="1"/>
+</svg>"##;
+    assert_eq!(render(&red_with_blue_corner(), 2, &options), expected);
+}
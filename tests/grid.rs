@@ -0,0 +1,179 @@
+use dont_tell_avali::{
+    build_document_rgba, rows_within_tile_count, tile_grid_size, triangle_cells_rgba,
+    view_box_size, DensityMap, RenderOptions, Shape, TileStyle, TriangleGrid,
+};
+
+/// Whether the point lies inside the triangle with the given corners, or on its outline
+fn contains(corners: [(f32, f32); 3], (x, y): (f32, f32)) -> bool {
+    let side =
+        |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0);
+    let [a, b, c] = corners;
+    let sides = [side(a, b), side(b, c), side(c, a)];
+    let tolerance = 1e-5;
+    sides.iter().all(|&side| side >= -tolerance) || sides.iter().all(|&side| side <= tolerance)
+}
+
+/// Checks that the triangles cover the strip along the right edge of the view box,
+/// poking out past it by no more than half a triangle
+fn assert_covers_right_edge(width: usize, height: usize, n_vertical_tris: usize) {
+    let rgba = vec![200; width * height * 4];
+    let options = RenderOptions {
+        style: TileStyle {
+            precision: None,
+            ..TileStyle::default()
+        },
+        ..RenderOptions::default()
+    };
+
+    let document = build_document_rgba(width, &rgba, n_vertical_tris, 0.1, &options);
+    let (view_width, view_height) = view_box_size(&document).unwrap();
+
+    let cells = triangle_cells_rgba(width, &rgba, n_vertical_tris, 0.1, &options);
+    let half_width = cells[0].0.half_width;
+    let max_x = cells
+        .iter()
+        .flat_map(|(triangle, _)| triangle.corners())
+        .map(|(x, _)| x)
+        .fold(f32::MIN, f32::max);
+    assert!(
+        max_x >= view_width && max_x <= view_width + half_width * 1.001,
+        "{}x{} image with {} rows: rightmost triangle ends at {}, view box is {} wide",
+        width,
+        height,
+        n_vertical_tris,
+        max_x,
+        view_width
+    );
+
+    // Points across the last half width of every row, where a missing triangle leaves a notch
+    let edge: Vec<_> = cells
+        .iter()
+        .filter(|(triangle, _)| triangle.x + triangle.half_width >= view_width - half_width)
+        .collect();
+    let steps = 20;
+    for i in 0..=steps {
+        for j in 0..=steps * n_vertical_tris {
+            let x = view_width - half_width * i as f32 / steps as f32;
+            let y = view_height * j as f32 / (steps * n_vertical_tris) as f32;
+            assert!(
+                edge.iter()
+                    .any(|(triangle, _)| contains(triangle.corners(), (x, y))),
+                "{}x{} image with {} rows: ({}, {}) is not covered",
+                width,
+                height,
+                n_vertical_tris,
+                x,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn right_edge_is_covered() {
+    assert_covers_right_edge(40, 40, 4);
+    assert_covers_right_edge(64, 32, 5);
+    assert_covers_right_edge(1280, 720, 30);
+}
+
+#[test]
+fn every_row_has_the_same_number_of_triangles() {
+    let rgba = vec![50; 64 * 32 * 4];
+    let cells = triangle_cells_rgba(64, &rgba, 6, 0.1, &RenderOptions::default());
+    let per_row = cells
+        .iter()
+        .filter(|(triangle, _)| triangle.row == 0)
+        .count();
+
+    assert_eq!(cells.len(), per_row * 6);
+    assert!(cells.iter().all(|(triangle, _)| triangle.col < per_row));
+}
+
+#[test]
+fn grid_iterator_matches_layout() {
+    let rgba = vec![50; 64 * 32 * 4];
+    let options = RenderOptions::default();
+    let cells = triangle_cells_rgba(64, &rgba, 6, 0.1, &options);
+    let grid = TriangleGrid::new(64, 32, 6, 0.1, &options);
+
+    assert_eq!(grid.len(), grid.n_rows() * grid.n_cols());
+    let triangles: Vec<_> = grid.map(|cell| cell.triangle).collect();
+    let expected: Vec<_> = cells.iter().map(|&(triangle, _)| triangle).collect();
+    assert_eq!(triangles, expected);
+}
+
+#[test]
+fn grid_row_only_visits_that_row() {
+    let grid = TriangleGrid::new(64, 32, 6, 0.1, &RenderOptions::default());
+    let n_cols = grid.n_cols();
+    let row: Vec<_> = grid.row(3).collect();
+
+    assert_eq!(row.len(), n_cols);
+    assert!(row.iter().all(|cell| cell.triangle.row == 3));
+    assert!(row
+        .iter()
+        .all(|cell| cell.pixel_min.1 == 16 && cell.pixel_max.1 == 21));
+}
+
+#[test]
+fn density_map_splits_where_bright() {
+    let rgba = vec![50; 64 * 32 * 4];
+    let plain = triangle_cells_rgba(64, &rgba, 4, 0.1, &RenderOptions::default());
+
+    let with_map = |level: u8| {
+        let map = DensityMap::from_rgba(2, &[level, level, level, 255].repeat(2), 1);
+        let options = RenderOptions {
+            density_map: Some(map),
+            ..RenderOptions::default()
+        };
+        triangle_cells_rgba(64, &rgba, 4, 0.1, &options).len()
+    };
+
+    assert_eq!(with_map(0), plain.len());
+    assert_eq!(with_map(255), plain.len() * 4);
+}
+
+#[test]
+fn stagger_shifts_odd_rows() {
+    let options = RenderOptions {
+        stagger: 0.5,
+        ..RenderOptions::default()
+    };
+    let aligned = TriangleGrid::new(64, 32, 6, 0.1, &RenderOptions::default());
+    let staggered = TriangleGrid::new(64, 32, 6, 0.1, &options);
+    let half_width = staggered.half_width();
+
+    assert!((staggered.view_width() - aligned.view_width() - half_width).abs() < 1e-6);
+    for (plain, shifted) in aligned.zip(staggered) {
+        let offset = shifted.triangle.x - plain.triangle.x;
+        if plain.triangle.row % 2 == 0 {
+            assert_eq!(offset, 0.);
+        } else {
+            assert!((offset - half_width).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn fitted_rows_stay_within_the_tile_count() {
+    for shape in [Shape::Triangle, Shape::Hex, Shape::Square, Shape::Diamond] {
+        let options = RenderOptions {
+            shape,
+            ..RenderOptions::default()
+        };
+        for max_tiles in [1, 2, 7, 50, 333, 1000, 4096] {
+            let Some(rows) = rows_within_tile_count(640, 480, 100, 0.1, &options, max_tiles) else {
+                // Only when a single row is already over the count
+                let (n_cols, _) = tile_grid_size(640, 480, 1, 0.1, &options);
+                assert!(n_cols > max_tiles);
+                continue;
+            };
+            let (n_cols, n_rows) = tile_grid_size(640, 480, rows, 0.1, &options);
+            assert!(n_cols * n_rows <= max_tiles, "{:?}: {} rows", shape, rows);
+
+            // One more row would have been too many, unless the limit was never reached
+            let (n_cols, n_rows) = tile_grid_size(640, 480, rows + 1, 0.1, &options);
+            assert!(rows == 100 || n_cols * n_rows > max_tiles);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use common::encode_png;
+use dont_tell_avali::{
+    composite_over, is_supported_image, load_image_rgb, load_input_rgba, load_png_rgb,
+    load_raw_input_rgba, load_raw_rgb, open_input_file, open_input_file_mapping, InputFile,
+};
+use png::ColorType;
+use std::io::Read;
+
+#[test]
+fn rgb_is_unchanged() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let png = encode_png(2, 2, ColorType::Rgb, &data);
+    assert_eq!(load_png_rgb(&png[..]).unwrap(), (2, data.to_vec()));
+}
+
+#[test]
+fn rgba_drops_alpha() {
+    let data = [10, 20, 30, 0, 40, 50, 60, 128, 70, 80, 90, 255];
+    let png = encode_png(3, 1, ColorType::Rgba, &data);
+    let expected = vec![10, 20, 30, 40, 50, 60, 70, 80, 90];
+    assert_eq!(load_png_rgb(&png[..]).unwrap(), (3, expected));
+}
+
+#[test]
+fn grayscale_is_replicated() {
+    let data = [0, 100, 200, 255];
+    let png = encode_png(2, 2, ColorType::Grayscale, &data);
+    let expected = vec![0, 0, 0, 100, 100, 100, 200, 200, 200, 255, 255, 255];
+    assert_eq!(load_png_rgb(&png[..]).unwrap(), (2, expected));
+}
+
+#[test]
+fn grayscale_alpha_is_replicated_and_drops_alpha() {
+    let data = [0, 255, 100, 0, 200, 128];
+    let png = encode_png(1, 3, ColorType::GrayscaleAlpha, &data);
+    let expected = vec![0, 0, 0, 100, 100, 100, 200, 200, 200];
+    assert_eq!(load_png_rgb(&png[..]).unwrap(), (1, expected));
+}
+
+#[cfg(feature = "pnm")]
+#[test]
+fn pnm_gray_is_replicated() {
+    let mut pgm = b"P5\n2 1\n255\n".to_vec();
+    pgm.extend([0, 200]);
+    let expected = vec![0, 0, 0, 200, 200, 200];
+    assert_eq!(
+        dont_tell_avali::load_pnm_rgb(&pgm[..]).unwrap(),
+        (2, expected)
+    );
+}
+
+#[cfg(feature = "pnm")]
+#[test]
+fn pnm_rgb_is_unchanged() {
+    let data = [1, 2, 3, 4, 5, 6];
+    let mut ppm = b"P6\n1 2\n255\n".to_vec();
+    ppm.extend(data);
+    assert_eq!(
+        dont_tell_avali::load_pnm_rgb(&ppm[..]).unwrap(),
+        (1, data.to_vec())
+    );
+}
+
+#[test]
+fn raw_rgb_is_unchanged() {
+    let data = [1, 2, 3, 4, 5, 6];
+    assert_eq!(load_raw_rgb(&data[..], 2, 1).unwrap(), (2, data.to_vec()));
+}
+
+#[test]
+fn raw_of_the_wrong_length_is_rejected() {
+    let data = [1, 2, 3, 4, 5];
+    assert!(load_raw_rgb(&data[..], 2, 1).is_err());
+}
+
+#[cfg(feature = "tga")]
+/// Header of a top-left origin TGA image of the given type and pixel depth
+fn tga_header(image_type: u8, width: u8, height: u8, depth: u8) -> Vec<u8> {
+    let mut header = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    header.extend([width, 0, height, 0, depth, 0x20]);
+    header
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_bgr_is_swapped() {
+    let mut tga = tga_header(2, 2, 1, 24);
+    tga.extend([3, 2, 1, 6, 5, 4]);
+    assert_eq!(
+        dont_tell_avali::load_tga_rgb(&tga[..]).unwrap(),
+        (2, vec![1, 2, 3, 4, 5, 6])
+    );
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_rle_bgra_is_expanded_and_drops_alpha() {
+    let mut tga = tga_header(10, 3, 1, 32);
+    // A run of two pixels, then a single literal pixel
+    tga.extend([0x81, 30, 20, 10, 255, 0x00, 60, 50, 40, 128]);
+    let expected = vec![10, 20, 30, 10, 20, 30, 40, 50, 60];
+    assert_eq!(
+        dont_tell_avali::load_tga_rgb(&tga[..]).unwrap(),
+        (3, expected)
+    );
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn webp_is_decoded() {
+    use image::codecs::webp::WebPEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let mut webp = vec![];
+    WebPEncoder::new_lossless(&mut webp)
+        .write_image(&data, 2, 2, ExtendedColorType::Rgb8)
+        .unwrap();
+    assert_eq!(
+        dont_tell_avali::load_webp_rgb(&webp[..]).unwrap(),
+        (2, data.to_vec())
+    );
+}
+
+#[cfg(feature = "tiff")]
+#[test]
+fn tiff_is_decoded() {
+    use image::codecs::tiff::TiffEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let encode = |data: &[u8], color| {
+        let mut tiff = std::io::Cursor::new(vec![]);
+        TiffEncoder::new(&mut tiff)
+            .write_image(data, 2, 1, color)
+            .unwrap();
+        tiff.into_inner()
+    };
+
+    let rgb = encode(&[1, 2, 3, 4, 5, 6], ExtendedColorType::Rgb8);
+    assert_eq!(
+        dont_tell_avali::load_tiff_rgb(&rgb[..]).unwrap(),
+        (2, vec![1, 2, 3, 4, 5, 6])
+    );
+    let gray = encode(&[7, 8], ExtendedColorType::L8);
+    assert_eq!(
+        dont_tell_avali::load_tiff_rgb(&gray[..]).unwrap(),
+        (2, vec![7, 7, 7, 8, 8, 8])
+    );
+
+    // 16 bits per sample is more than the mosaic keeps
+    let deep = encode(&[0; 12], ExtendedColorType::Rgb16);
+    let error = dont_tell_avali::load_tiff_rgb(&deep[..]).unwrap_err();
+    assert!(error.to_string().contains("not supported"), "{}", error);
+}
+
+#[cfg(not(feature = "tiff"))]
+#[test]
+fn disabled_tiff_names_its_feature() {
+    let error = dont_tell_avali::load_image_rgb("scan.tif").unwrap_err();
+    assert!(error.to_string().contains("`tiff` feature"), "{}", error);
+}
+
+#[cfg(not(feature = "webp"))]
+#[test]
+fn disabled_format_names_its_feature() {
+    let error = dont_tell_avali::load_image_rgb("missing.webp").unwrap_err();
+    assert!(error.to_string().contains("`webp` feature"), "{}", error);
+}
+
+#[test]
+fn supported_images_are_picked_by_extension() {
+    assert!(is_supported_image("photo.PNG"));
+    assert!(!is_supported_image("notes.txt"));
+    assert!(!is_supported_image("no_extension"));
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn gif_reads_first_frame() {
+    use image::codecs::gif::GifEncoder;
+    use image::{Frame, RgbaImage};
+
+    let frame = |data: Vec<u8>| Frame::new(RgbaImage::from_raw(2, 1, data).unwrap());
+    let mut gif = vec![];
+    GifEncoder::new(&mut gif)
+        .encode_frames([
+            frame(vec![255, 0, 0, 255, 0, 0, 255, 255]),
+            frame(vec![0, 255, 0, 255, 0, 255, 0, 255]),
+        ])
+        .unwrap();
+    let expected = vec![255, 0, 0, 0, 0, 255];
+    assert_eq!(
+        dont_tell_avali::load_gif_rgb(&gif[..]).unwrap(),
+        (2, expected)
+    );
+}
+
+#[test]
+fn composite_blends_by_alpha() {
+    let rgba = [200, 100, 0, 255, 200, 100, 0, 0, 200, 100, 0, 128];
+    let expected = vec![200, 100, 0, 255, 255, 255, 227, 177, 127];
+    assert_eq!(composite_over(&rgba, [255, 255, 255]), expected);
+}
+
+#[test]
+fn loads_from_a_file() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let path = std::env::temp_dir().join(format!("load-test-{}.png", std::process::id()));
+    std::fs::write(&path, encode_png(2, 2, ColorType::Rgb, &data)).unwrap();
+
+    let loaded = load_image_rgb(&path);
+    let mut bytes = vec![];
+    let read = open_input_file(&path).and_then(|mut file| Ok(file.read_to_end(&mut bytes)?));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.unwrap(), (2, data.to_vec()));
+    read.unwrap();
+    assert_eq!(&bytes[..4], b"\x89PNG");
+}
+
+#[test]
+fn mapped_files_are_loaded_in_place() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let dir = std::env::temp_dir();
+    let png = dir.join(format!("mapped-test-{}.png", std::process::id()));
+    let raw = dir.join(format!("mapped-test-{}.rgb", std::process::id()));
+    std::fs::write(&png, encode_png(2, 2, ColorType::Rgb, &data)).unwrap();
+    std::fs::write(&raw, data).unwrap();
+
+    let mapped_png = open_input_file_mapping(&png, 0).unwrap();
+    let mapped_raw = open_input_file_mapping(&raw, 0).unwrap();
+    let short_raw = open_input_file_mapping(&raw, 0).unwrap();
+    let was_mapped = [&mapped_png, &mapped_raw].map(|file| matches!(file, InputFile::Mapped(_)));
+    let loaded_png = load_input_rgba(mapped_png, &png);
+    let loaded_raw = load_raw_input_rgba(mapped_raw, 2, 2);
+    let short = load_raw_input_rgba(short_raw, 3, 2);
+    std::fs::remove_file(&png).unwrap();
+    std::fs::remove_file(&raw).unwrap();
+
+    let rgba = vec![1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255, 10, 11, 12, 255];
+    assert_eq!(was_mapped, [true, true]);
+    assert_eq!(loaded_png.unwrap(), (2, rgba.clone()));
+    assert_eq!(loaded_raw.unwrap(), (2, rgba));
+    assert!(short.is_err());
+}
@@ -0,0 +1,93 @@
+use dont_tell_avali::{load_palette, nearest_palette, parse_hex_color, PaletteSpace};
+
+#[test]
+fn six_digit_colors_are_parsed() {
+    assert_eq!(parse_hex_color("#FF8800").unwrap(), [255, 136, 0]);
+    assert_eq!(parse_hex_color("12abEF").unwrap(), [0x12, 0xAB, 0xEF]);
+}
+
+#[test]
+fn three_digit_colors_repeat_each_digit() {
+    assert_eq!(parse_hex_color("#F80").unwrap(), [255, 136, 0]);
+    assert_eq!(parse_hex_color("abc").unwrap(), [0xAA, 0xBB, 0xCC]);
+}
+
+#[test]
+fn malformed_colors_are_rejected() {
+    for text in ["", "#", "#12345", "#1234567", "#GG0000", "red"] {
+        let error = parse_hex_color(text).unwrap_err();
+        assert!(error.to_string().contains("Invalid hex color"), "{}", error);
+    }
+}
+
+#[test]
+fn nearest_entry_is_picked() {
+    let palette = [[0, 0, 0], [255, 255, 255], [255, 0, 0]];
+    assert_eq!(
+        nearest_palette([200, 40, 30], &palette, PaletteSpace::Rgb),
+        [255, 0, 0]
+    );
+    assert_eq!(
+        nearest_palette([90, 90, 90], &palette, PaletteSpace::Rgb),
+        [0, 0, 0]
+    );
+    assert_eq!(
+        nearest_palette([220, 210, 200], &palette, PaletteSpace::Lab),
+        [255, 255, 255]
+    );
+}
+
+#[test]
+fn empty_palette_keeps_the_color() {
+    assert_eq!(
+        nearest_palette([1, 2, 3], &[], PaletteSpace::Rgb),
+        [1, 2, 3]
+    );
+}
+
+#[test]
+fn lab_matches_by_perceived_difference() {
+    // In RGB the dark gray is nearer the green, but it looks nearer the lighter gray
+    let palette = [[128, 128, 128], [0, 128, 0]];
+    assert_eq!(
+        nearest_palette([60, 60, 60], &palette, PaletteSpace::Rgb),
+        [0, 128, 0]
+    );
+    assert_eq!(
+        nearest_palette([60, 60, 60], &palette, PaletteSpace::Lab),
+        [128, 128, 128]
+    );
+}
+
+#[test]
+fn palette_file_is_loaded() {
+    let path = std::env::temp_dir().join(format!("palette-test-{}.txt", std::process::id()));
+    std::fs::write(&path, "#000\n\n  #FF8800  \nabc\n").unwrap();
+    let palette = load_palette(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        palette.unwrap(),
+        vec![[0, 0, 0], [255, 136, 0], [0xAA, 0xBB, 0xCC]]
+    );
+}
+
+#[test]
+fn bad_palette_file_names_the_line() {
+    let path = std::env::temp_dir().join(format!("palette-bad-{}.txt", std::process::id()));
+    std::fs::write(&path, "#000\nnot a color\n").unwrap();
+    let error = load_palette(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(format!("{:#}", error).contains("On line 2"), "{:#}", error);
+}
+
+#[test]
+fn empty_palette_file_is_rejected() {
+    let path = std::env::temp_dir().join(format!("palette-empty-{}.txt", std::process::id()));
+    std::fs::write(&path, "\n  \n").unwrap();
+    let error = load_palette(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(error.to_string().contains("has no colors"), "{}", error);
+}
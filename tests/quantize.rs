@@ -0,0 +1,68 @@
+use dont_tell_avali::{build_document_rgba, quantize_kmeans, RenderOptions};
+
+#[test]
+fn kmeans_finds_both_colors_of_a_two_color_image() {
+    let (red, blue) = ([200, 30, 30], [20, 40, 220]);
+    let colors: Vec<[u8; 3]> = (0..40)
+        .map(|i| if i % 3 == 0 { red } else { blue })
+        .collect();
+    assert_eq!(quantize_kmeans(&colors, 2), colors);
+}
+
+#[test]
+fn kmeans_snaps_each_color_to_its_cluster_centroid() {
+    let colors = [
+        [10, 10, 10],
+        [20, 20, 20],
+        [12, 14, 16],
+        [240, 200, 10],
+        [250, 210, 20],
+    ];
+    let dark = [14, 15, 15];
+    let yellow = [245, 205, 15];
+    assert_eq!(
+        quantize_kmeans(&colors, 2),
+        vec![dark, dark, dark, yellow, yellow]
+    );
+}
+
+#[test]
+fn kmeans_leaves_few_colors_alone() {
+    let colors = [[1, 2, 3], [4, 5, 6], [1, 2, 3]];
+    assert_eq!(quantize_kmeans(&colors, 4), colors.to_vec());
+}
+
+#[test]
+fn dithering_mid_gray_mixes_black_and_white_evenly() {
+    let (width, height) = (32, 32);
+    let rgba = [128, 128, 128, 255].repeat(width * height);
+    let options = RenderOptions {
+        palette: Some(vec![[0, 0, 0], [255, 255, 255]]),
+        dither: true,
+        ..Default::default()
+    };
+    let document = build_document_rgba(width, &rgba, 16, 1., &options).to_string();
+
+    let black = document.matches(r##"fill="#000""##).count();
+    let white = document.matches(r##"fill="#FFF""##).count();
+    let share = white as f32 / (black + white) as f32;
+    assert!(black > 0 && white > 0);
+    assert!(
+        (share - 0.5).abs() < 0.05,
+        "{} black, {} white",
+        black,
+        white
+    );
+}
+
+#[test]
+fn without_dithering_mid_gray_snaps_to_one_color() {
+    let rgba = [128, 128, 128, 255].repeat(16 * 16);
+    let options = RenderOptions {
+        palette: Some(vec![[0, 0, 0], [255, 255, 255]]),
+        ..Default::default()
+    };
+    let document = build_document_rgba(16, &rgba, 8, 1., &options).to_string();
+    assert!(!document.contains(r##"fill="#000""##));
+    assert!(document.contains(r##"fill="#FFF""##));
+}